@@ -0,0 +1,281 @@
+use std::hint::black_box;
+use std::io::Cursor;
+use std::time::{Duration, Instant};
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use tempfile::TempDir;
+
+use rusty_merkle_tree::bitcoin::{self, PartialMerkleTree};
+use rusty_merkle_tree::export;
+use rusty_merkle_tree::hashing::{self, DomainSeparation};
+use rusty_merkle_tree::store::{FileStore, MemoryStore};
+use rusty_merkle_tree::sync::Message;
+#[cfg(feature = "blake3")]
+use rusty_merkle_tree::HashFunction;
+use rusty_merkle_tree::MerkleTree;
+
+const SIZES: [usize; 3] = [1_000, 100_000, 1_000_000];
+
+/// The single adds are measured on the big trees only, where the depth makes the difference
+const ADD_SIZES: [usize; 2] = [100_000, 1_000_000];
+
+/// Adds made to a tree before starting over from a fresh one, so every add is measured on a tree of about the same
+/// size instead of one that keeps growing with the iterations
+const ADDS_PER_TREE: usize = 1_000;
+
+/// How the nodes are hashed. Every benchmark runs with each backend, named in its id so the report compares them
+/// The BLAKE3 ones need the blake3 feature: `cargo bench --features blake3`
+#[derive(Clone, Copy)]
+enum Backend {
+    Sha256,
+    Sha256Rfc6962,
+    #[cfg(feature = "blake3")]
+    Blake3,
+    #[cfg(feature = "blake3")]
+    Blake3Keyed,
+}
+
+const BACKENDS: &[Backend] = &[
+    Backend::Sha256,
+    Backend::Sha256Rfc6962,
+    #[cfg(feature = "blake3")]
+    Backend::Blake3,
+    #[cfg(feature = "blake3")]
+    Backend::Blake3Keyed,
+];
+
+impl Backend {
+    fn name(self) -> &'static str {
+        match self {
+            Backend::Sha256 => "sha256",
+            Backend::Sha256Rfc6962 => "sha256-rfc6962",
+            #[cfg(feature = "blake3")]
+            Backend::Blake3 => "blake3",
+            #[cfg(feature = "blake3")]
+            Backend::Blake3Keyed => "blake3-keyed",
+        }
+    }
+
+    fn domain(self) -> DomainSeparation {
+        match self {
+            Backend::Sha256 => DomainSeparation::default(),
+            Backend::Sha256Rfc6962 => DomainSeparation::rfc6962(),
+            #[cfg(feature = "blake3")]
+            Backend::Blake3 => DomainSeparation::default().with_hash_function(HashFunction::Blake3),
+            #[cfg(feature = "blake3")]
+            Backend::Blake3Keyed => DomainSeparation::blake3_keyed([0; 32], [1; 32]).unwrap(),
+        }
+    }
+
+    fn configure(self, tree: MerkleTree) -> MerkleTree {
+        tree.with_domain_separation(self.domain())
+    }
+
+    fn leaves(self, amount: usize) -> Vec<String> {
+        let domain = self.domain();
+        (0..amount)
+            .map(|i| hashing::hash_leaf(&domain, i.to_string().as_bytes()))
+            .collect()
+    }
+}
+
+/// Where the nodes are kept. The file store lives in a temporary directory removed with the layout
+enum Layout {
+    Memory,
+    File(TempDir),
+}
+
+impl Layout {
+    fn all() -> [Layout; 2] {
+        [Layout::Memory, Layout::File(tempfile::tempdir().unwrap())]
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Layout::Memory => "memory",
+            Layout::File(_) => "file",
+        }
+    }
+
+    /// An empty tree on this layout, for the given amount of leaves. The file trees of a size share their file, so
+    /// the previous one must be dropped before creating another
+    fn tree(&self, backend: Backend, size: usize) -> MerkleTree {
+        let tree = match self {
+            Layout::Memory => MerkleTree::new()
+                .with_store(MemoryStore::default())
+                .unwrap(),
+            Layout::File(dir) => {
+                let path = dir.path().join(format!("nodes-{size}"));
+                MerkleTree::new()
+                    .with_store(FileStore::create(path).unwrap())
+                    .unwrap()
+            }
+        };
+        backend.configure(tree)
+    }
+}
+
+/// Id of a benchmark on a tree: backend/layout/size
+fn id(backend: Backend, layout: &Layout, size: usize) -> BenchmarkId {
+    BenchmarkId::new(format!("{}/{}", backend.name(), layout.name()), size)
+}
+
+/// Hashing the data of a leaf, the cost of add_unhashed and build with unhashed values
+fn bench_hash_leaf(c: &mut Criterion) {
+    let mut group = c.benchmark_group("hash_leaf");
+    for &backend in BACKENDS {
+        let domain = backend.domain();
+        for size in [64, 1_024, 16_384] {
+            let data = vec![7; size];
+            group.throughput(Throughput::Bytes(size as u64));
+            group.bench_function(BenchmarkId::new(backend.name(), size), |b| {
+                b.iter(|| hashing::hash_leaf(&domain, black_box(&data)))
+            });
+        }
+    }
+    group.finish();
+}
+
+fn bench_build(c: &mut Criterion) {
+    let mut group = c.benchmark_group("build");
+    group.sample_size(10);
+    for &backend in BACKENDS {
+        for layout in Layout::all() {
+            for size in SIZES {
+                let leaves = backend.leaves(size);
+                group.throughput(Throughput::Elements(size as u64));
+                group.bench_function(id(backend, &layout, size), |b| {
+                    b.iter_batched(
+                        || leaves.clone(),
+                        |leaves| layout.tree(backend, size).with_leaves(leaves),
+                        BatchSize::PerIteration,
+                    )
+                });
+            }
+        }
+    }
+    group.finish();
+}
+
+fn bench_add(c: &mut Criterion) {
+    let mut group = c.benchmark_group("add");
+    for &backend in BACKENDS {
+        for layout in Layout::all() {
+            for size in ADD_SIZES {
+                let leaves = backend.leaves(size + ADDS_PER_TREE);
+                let fresh = || {
+                    layout
+                        .tree(backend, size)
+                        .with_leaves(leaves[..size].to_vec())
+                };
+                let mut tree = fresh();
+
+                // Only the adds are timed, starting over from a fresh tree doesn't count
+                group.bench_function(id(backend, &layout, size), |b| {
+                    b.iter_custom(|iters| {
+                        let mut elapsed = Duration::ZERO;
+                        for _ in 0..iters {
+                            if tree.len() == leaves.len() {
+                                // The old tree releases the file before the fresh one empties it
+                                tree = MerkleTree::new();
+                                tree = fresh();
+                            }
+                            let leaf = leaves[tree.len()].clone();
+                            let start = Instant::now();
+                            tree.add(leaf);
+                            elapsed += start.elapsed();
+                        }
+                        elapsed
+                    })
+                });
+            }
+        }
+    }
+    group.finish();
+}
+
+fn bench_proofs(c: &mut Criterion) {
+    let mut generate = c.benchmark_group("generate_proof");
+    let mut trees = Vec::new();
+    // The directories of the file trees, kept until they are verified
+    let mut layouts = Vec::new();
+    for &backend in BACKENDS {
+        for layout in Layout::all() {
+            for size in SIZES {
+                let tree = layout.tree(backend, size).with_leaves(backend.leaves(size));
+                // A leaf in the right half, so with every padding the proof has a sibling at each level
+                let index = size * 3 / 4;
+                generate.bench_function(id(backend, &layout, size), |b| {
+                    b.iter(|| tree.generate_proof(black_box(index)))
+                });
+                trees.push((id(backend, &layout, size), tree, index));
+            }
+            layouts.push(layout);
+        }
+    }
+    generate.finish();
+
+    let mut verify = c.benchmark_group("verify");
+    for (id, tree, index) in &trees {
+        let proof = tree.generate_proof(*index);
+        let leaf = tree.leaf(*index).unwrap();
+        verify.bench_function(id.clone(), |b| {
+            b.iter_batched(
+                || (proof.clone(), leaf.clone()),
+                |(proof, leaf)| tree.verify(proof, leaf, black_box(*index)),
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    verify.finish();
+}
+
+fn bench_serialization(c: &mut Criterion) {
+    let mut group = c.benchmark_group("serialization");
+    // A Bitcoin block can't have many more than 10k transactions
+    for size in [1_000, 10_000] {
+        let txids: Vec<[u8; 32]> = (0..size as u32)
+            .map(|i| bitcoin::double_sha256(&i.to_le_bytes()))
+            .collect();
+        let matches: Vec<bool> = (0..size).map(|i| i % 100 == 0).collect();
+        let partial_tree = PartialMerkleTree::build(&txids, &matches).unwrap();
+        let serialized = partial_tree.serialize();
+
+        group.throughput(Throughput::Elements(size as u64));
+        group.bench_function(BenchmarkId::new("bitcoin-serialize", size), |b| {
+            b.iter(|| partial_tree.serialize())
+        });
+        group.bench_function(BenchmarkId::new("bitcoin-deserialize", size), |b| {
+            b.iter(|| PartialMerkleTree::deserialize(black_box(&serialized)).unwrap())
+        });
+
+        let message = Message::Leaves {
+            first: 0,
+            leaves: Backend::Sha256.leaves(size),
+        };
+        let mut written = Vec::new();
+        message.write_to(&mut written).unwrap();
+        group.bench_function(BenchmarkId::new("sync-write", size), |b| {
+            b.iter(|| message.write_to(&mut Vec::new()).unwrap())
+        });
+        group.bench_function(BenchmarkId::new("sync-read", size), |b| {
+            b.iter(|| Message::read_from(&mut Cursor::new(black_box(&written))).unwrap())
+        });
+
+        let tree = MerkleTree::new().with_leaves(Backend::Sha256.leaves(size));
+        group.bench_function(BenchmarkId::new("json-export", size), |b| {
+            b.iter(|| export::to_json(black_box(&tree)))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_hash_leaf,
+    bench_build,
+    bench_add,
+    bench_proofs,
+    bench_serialization
+);
+criterion_main!(benches);
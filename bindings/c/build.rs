@@ -0,0 +1,14 @@
+/// Writes rusty_merkle_tree.h to the OUT_DIR. Only the C bindings are parsed, the rest of the crate is not part of it
+fn main() {
+    println!("cargo:rerun-if-changed=../../src/capi.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+
+    let out_dir = std::env::var("OUT_DIR").expect("cargo sets OUT_DIR");
+    let config = cbindgen::Config::from_file("cbindgen.toml").expect("cbindgen.toml is valid");
+    cbindgen::Builder::new()
+        .with_config(config)
+        .with_src("../../src/capi.rs")
+        .generate()
+        .expect("the header could not be generated")
+        .write_to_file(std::path::Path::new(&out_dir).join("rusty_merkle_tree.h"));
+}
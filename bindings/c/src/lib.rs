@@ -0,0 +1,2 @@
+//! Only re-exports the bindings of src/capi.rs, so they are built into a library C can link
+pub use rusty_merkle_tree::capi::*;
@@ -0,0 +1,34 @@
+use std::path::Path;
+use std::process::Command;
+
+use rusty_merkle_tree::MerkleTree;
+
+/// Compiles tests/capi_test.c against the generated header and the cdylib, and runs it
+#[test]
+fn test_01_c_program_gets_the_same_root() {
+    let test_exe = std::env::current_exe().unwrap();
+    // The cdylib is built next to the test executables
+    let lib_dir = test_exe.parent().unwrap();
+    let program = Path::new(env!("CARGO_TARGET_TMPDIR")).join("capi_test");
+
+    let compiled = Command::new(std::env::var("CC").unwrap_or("cc".to_string()))
+        .arg(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/capi_test.c"))
+        .arg("-I")
+        .arg(env!("OUT_DIR"))
+        .arg("-L")
+        .arg(lib_dir)
+        .arg(format!("-Wl,-rpath,{}", lib_dir.display()))
+        .args(["-lrusty_merkle_tree_c", "-Wall", "-Werror", "-o"])
+        .arg(&program)
+        .status()
+        .unwrap();
+    assert!(compiled.success());
+
+    let root = MerkleTree::build(vec!["a", "b", "c"], true).root().unwrap();
+    let output = Command::new(&program).arg(root).output().unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
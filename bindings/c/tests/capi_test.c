@@ -0,0 +1,50 @@
+/* Builds a tree through the C bindings and checks it against the root passed as the first argument */
+#include <stdbool.h>
+#include <stdio.h>
+#include <string.h>
+
+#include "rusty_merkle_tree.h"
+
+#define CHECK(condition)                                                    \
+    do {                                                                    \
+        if (!(condition)) {                                                 \
+            fprintf(stderr, "%s:%d: %s failed\n", __FILE__, __LINE__, #condition); \
+            return 1;                                                       \
+        }                                                                   \
+    } while (0)
+
+int main(int argc, char **argv) {
+    CHECK(argc == 2);
+    const char *leaf_b = "3e23e8160039594a33894f6564e1b1348bbd7a0088d42c4acb73eeaed59c009d";
+
+    MtTree *tree = mt_new();
+    CHECK(tree != NULL);
+    char root[MT_HASH_SIZE];
+    CHECK(mt_root(tree, root) == MT_STATUS_EMPTY_TREE);
+
+    CHECK(mt_add_unhashed(tree, "a") == MT_STATUS_OK);
+    CHECK(mt_add(tree, leaf_b) == MT_STATUS_OK);
+    CHECK(mt_add_unhashed(tree, "c") == MT_STATUS_OK);
+    CHECK(mt_add(tree, "not a hash") == MT_STATUS_INVALID_HASH);
+    CHECK(mt_add(NULL, leaf_b) == MT_STATUS_NULL_POINTER);
+
+    CHECK(mt_root(tree, root) == MT_STATUS_OK);
+    CHECK(strcmp(root, argv[1]) == 0);
+
+    size_t proof_len = 0;
+    CHECK(mt_proof(tree, 1, NULL, 0, &proof_len) == MT_STATUS_BUFFER_TOO_SMALL);
+    CHECK(proof_len == 2);
+    char proof[2 * MT_HASH_SIZE];
+    CHECK(mt_proof(tree, 1, proof, 2, &proof_len) == MT_STATUS_OK);
+    CHECK(mt_proof(tree, 3, proof, 2, &proof_len) == MT_STATUS_INDEX_OUT_OF_RANGE);
+
+    bool valid = false;
+    CHECK(mt_verify(tree, proof, proof_len, leaf_b, 1, &valid) == MT_STATUS_OK);
+    CHECK(valid);
+    CHECK(mt_verify(tree, proof, proof_len, leaf_b, 0, &valid) == MT_STATUS_OK);
+    CHECK(!valid);
+    CHECK(mt_verify(tree, proof, proof_len, "x", 1, &valid) == MT_STATUS_INVALID_HASH);
+
+    mt_free(tree);
+    return 0;
+}
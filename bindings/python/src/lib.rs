@@ -0,0 +1,2 @@
+//! Only re-exports the bindings of src/python.rs, so maturin builds them into an extension module
+pub use rusty_merkle_tree::python::*;
@@ -0,0 +1,2 @@
+//! Only re-exports the bindings of src/wasm.rs, so wasm-pack builds them into a package
+pub use rusty_merkle_tree::wasm::*;
@@ -0,0 +1,32 @@
+// Plain tests natively, and under node with wasm-pack test --node bindings/wasm
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen_test::wasm_bindgen_test as test;
+
+use rusty_merkle_tree_wasm::{verify_proof, WasmMerkleTree};
+
+const HASH_B: &str = "3e23e8160039594a33894f6564e1b1348bbd7a0088d42c4acb73eeaed59c009d";
+
+#[test]
+fn test_01_trees_built_from_strings_generate_proofs_that_verify() {
+    let mut tree = WasmMerkleTree::new(vec!["a".to_string(), "b".to_string()], true);
+    tree.add_unhashed("c".to_string());
+    assert_eq!(3, tree.size());
+
+    let proof = tree.generate_proof(1).unwrap();
+    assert!(tree.verify(proof.clone(), HASH_B.to_string(), 1));
+    assert!(!tree.verify(proof, HASH_B.to_string(), 0));
+    assert_eq!(None, tree.generate_proof(3));
+}
+
+#[test]
+fn test_02_proofs_are_verified_with_only_the_root() {
+    let tree = WasmMerkleTree::new(vec![HASH_B.to_string(); 3], false);
+    let root = tree.root().unwrap();
+    let proof = tree.generate_proof(2).unwrap();
+    let leaf = || HASH_B.to_string();
+
+    assert!(verify_proof(&root, leaf(), 2, 3, proof.clone()));
+    assert!(!verify_proof(&root, leaf(), 2, 2, proof.clone()));
+    assert!(!verify_proof(&root, "ab".repeat(32), 2, 3, proof));
+    assert_eq!(None, WasmMerkleTree::new(vec![], true).root());
+}
@@ -0,0 +1,37 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rusty_merkle_tree::bitcoin::PartialMerkleTree;
+use rusty_merkle_tree::signed_tree_head::SignedTreeHead;
+use rusty_merkle_tree::sync::Message;
+
+// Everything that reads proofs sent by someone else: bitcoin partial merkle trees, signed tree heads and the messages
+// of the replica sync. Malformed input must be an error, and whatever is read must be written back the same
+fuzz_target!(|data: &[u8]| {
+    if let Ok(partial_tree) = PartialMerkleTree::deserialize(data) {
+        let _ = partial_tree.extract_matches();
+        assert_eq!(
+            Some(partial_tree.serialize()),
+            PartialMerkleTree::deserialize(&partial_tree.serialize())
+                .ok()
+                .map(|read| read.serialize())
+        );
+    }
+
+    if let Ok(serialized) = std::str::from_utf8(data) {
+        if let Ok(head) = SignedTreeHead::deserialize(serialized) {
+            let public = ed25519_dalek::SigningKey::from_bytes(&[7; 32]).verifying_key();
+            let _ = head.verify(&public);
+        }
+    }
+
+    let mut reader = data;
+    while let Ok(Some(message)) = Message::read_from(&mut reader) {
+        let mut written = Vec::new();
+        message.write_to(&mut written).unwrap();
+        assert_eq!(
+            Some(message),
+            Message::read_from(&mut written.as_slice()).ok().flatten()
+        );
+    }
+});
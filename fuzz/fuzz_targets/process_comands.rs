@@ -0,0 +1,29 @@
+#![no_main]
+
+// The commands of the REPL, built from its own sources as the binary has no library to depend on
+#[path = "../../src/bin/rusty-merkle-tree/commands.rs"]
+#[allow(dead_code)]
+mod commands;
+#[path = "../../src/bin/rusty-merkle-tree/errors.rs"]
+#[allow(dead_code)]
+mod errors;
+
+use libfuzzer_sys::fuzz_target;
+use rusty_merkle_tree::MerkleTree;
+
+/// Commands that read or write files are skipped, so the fuzzer doesn't write all over the disk
+const FILE_COMMANDS: [&str; 4] = ["export", "keygen", "sign-root", "verify-root"];
+
+// Every line of the input runs on the same tree, and no line can make the REPL panic
+fuzz_target!(|data: &[u8]| {
+    let Ok(input) = std::str::from_utf8(data) else {
+        return;
+    };
+    let mut tree = MerkleTree::new().with_leaf_index();
+    for line in input.lines().take(16) {
+        let command = line.split_ascii_whitespace().next().unwrap_or_default();
+        if !FILE_COMMANDS.contains(&command) {
+            let _ = commands::process_comands(line.to_string(), &mut tree);
+        }
+    }
+});
@@ -0,0 +1,71 @@
+#![no_main]
+
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use rusty_merkle_tree::{DomainSeparation, FixedProof, MerkleProof, MerkleTree, Padding};
+
+#[derive(Debug, Arbitrary)]
+struct Input {
+    leaves: Vec<String>,
+    padding: u8,
+    rfc6962: bool,
+    proof: Vec<String>,
+    leaf: String,
+    /// Signed like the indices of clients that don't check them, negative ones are cast as they would be
+    index: i64,
+    tree_size: u64,
+    siblings: Vec<[u8; 32]>,
+}
+
+// Proofs of untrusted clients: any proof, leaf and index is answered, and a proof is only valid for its own leaf
+fuzz_target!(|input: Input| {
+    let padding = match input.padding % 3 {
+        0 => Padding::DuplicateLast,
+        1 => Padding::Zero,
+        _ => Padding::Promote,
+    };
+    let domain = if input.rfc6962 {
+        DomainSeparation::rfc6962()
+    } else {
+        DomainSeparation::default()
+    };
+    let mut tree = MerkleTree::new()
+        .with_padding(padding)
+        .with_domain_separation(domain.clone());
+    for leaf in input.leaves.iter().take(64) {
+        tree.add_unhashed(leaf.clone());
+    }
+    let index = input.index as usize;
+
+    let valid = tree.verify(input.proof.clone(), input.leaf.clone(), index);
+    if valid {
+        assert!(index < tree.len());
+    }
+    let _ = tree.generate_proof(index);
+    let _ = tree
+        .snapshot()
+        .verify(input.proof.clone(), input.leaf.clone(), index);
+
+    let proof = MerkleProof {
+        index,
+        tree_size: input.tree_size as usize,
+        leaf: input.leaf,
+        siblings: input.proof,
+    };
+    if let Some(root) = tree.root() {
+        let _ = proof.verify(&root, padding, &domain);
+    }
+
+    let fixed = FixedProof {
+        index,
+        tree_size: input.tree_size as usize,
+        leaf: [0; 32],
+        siblings: &input.siblings,
+    };
+    let _ = fixed.verify(&[0; 32], padding, &domain);
+
+    if let Some(proof) = tree.prove(index) {
+        let root = tree.root().unwrap();
+        assert!(proof.verify(&root, padding, &domain));
+    }
+});
@@ -0,0 +1,406 @@
+use rusty_merkle_tree::bitcoin::{self, PartialMerkleTree};
+use rusty_merkle_tree::errors::BitcoinErrors;
+use rusty_merkle_tree::export;
+use rusty_merkle_tree::hashing::{self, DomainSeparation};
+use rusty_merkle_tree::merkle_tree::{MerkleTree, Padding};
+use rusty_merkle_tree::printer::{self, Layout, PrintOptions};
+use rusty_merkle_tree::signed_tree_head::{self, SignedTreeHead};
+
+use crate::errors::UserInterfaceErrors;
+
+pub fn process_comands(line: String, tree: &mut MerkleTree) -> Result<(), UserInterfaceErrors> {
+    let args: Vec<&str> = line.split_ascii_whitespace().collect();
+    let Some(command) = args.first() else {
+        return Ok(());
+    };
+
+    match *command {
+        "--help" => {
+            println!("  build - Usage: build <hash-1> <hash-2> ... <hash-n>");
+            println!("  build-unhashed - Usage: build-unhashed <unhashed-text-1> <unhashed-text-2> ... <unhashed-text-n>");
+            println!("  add-unhashed - Usage: add-unhashed unhashed-text");
+            println!("  add - Usage: add 32-bytes-hash");
+            println!("  verify - Usage: verify proof1 proof2 ... proofN seed index");
+            println!("  proof - Usage: proof index");
+            println!("  find - Usage: find 32-bytes-hash");
+            println!("  find-unhashed - Usage: find-unhashed unhashed-text");
+            println!("  print - Usage: print [--width N|full] [--vertical|--horizontal] [--levels N] [--proof index]");
+            println!("  root - Usage: root");
+            println!("  export - Usage: export --format dot|json <file> [--proof index]");
+            println!("  padding - Usage: padding duplicate-last|zero|promote");
+            println!("  domain - Usage: domain none|rfc6962|<leaf-tag> <node-tag>");
+            println!("  bitcoin-root - Usage: bitcoin-root <txid-1> <txid-2> ... <txid-n>");
+            println!("  bitcoin-proof - Usage: bitcoin-proof index <txid-1> <txid-2> ... <txid-n>");
+            println!("  bitcoin-verify - Usage: bitcoin-verify partial-tree merkle-root");
+            println!("  keygen - Usage: keygen <key-file> <pubkey-file>");
+            println!("  sign-root - Usage: sign-root --key <key-file>");
+            println!("  verify-root - Usage: verify-root --pubkey <pubkey-file> signed-tree-head");
+        }
+        "build" => {
+            // Usage: build <hash-1> <hash-2> ... <hash-n>
+            // The leaves are replaced in a single pass, keeping the chosen padding and domain
+            let hashes = args[1..].iter().map(|hash| hash.to_string()).collect();
+            *tree = std::mem::take(tree).with_leaves(hashes);
+        }
+        "build-unhashed" => {
+            // Usage: build <unhashed-text-1> <unhashed-text-2> ... <unhashed-text-n>
+            let domain = tree.domain_separation();
+            let hashes = args[1..]
+                .iter()
+                .map(|text| hashing::hash_leaf(domain, text.as_bytes()))
+                .collect();
+            *tree = std::mem::take(tree).with_leaves(hashes);
+        }
+        "add" => {
+            // Usage: add hash
+            if let Some(str) = args.get(1) {
+                tree.add(str.to_string());
+            } else {
+                return Err(UserInterfaceErrors::NotEnoughArgumentsError(
+                    "add hash".to_string(),
+                ));
+            }
+        }
+        "add-unhashed" => {
+            // Usage: add-unhashed unhashed-text
+            if args.len() >= 2 {
+                let text: String = Vec::from(&args[1..]).join(" ");
+                tree.add_unhashed(text);
+            } else {
+                return Err(UserInterfaceErrors::NotEnoughArgumentsError(
+                    "add-unhashed unhashed-text".to_string(),
+                ));
+            }
+        }
+        "verify" => {
+            // Usage: verify proof1 proof2 ... proofN seed index
+            if args.len() < 4 {
+                return Err(UserInterfaceErrors::NotEnoughArgumentsError(
+                    "verify proof1 proof2 ... proofN seed index".to_string(),
+                ));
+            }
+
+            let mut proof = Vec::new();
+            for item in args.iter().skip(1).take(args.len() - 3) {
+                proof.push((*item).to_string());
+            }
+            let leaf = args[args.len() - 2].to_string();
+
+            match args[args.len() - 1].to_string().parse() {
+                Ok(index) => {
+                    if tree.verify(proof, leaf, index) {
+                        println!("Proof has been verified");
+                    } else {
+                        println!("Proof has not been verified");
+                    }
+                }
+                Err(e) => {
+                    return Err(UserInterfaceErrors::NotCorrectTypeError(e));
+                }
+            }
+        }
+        "proof" => {
+            // Usage: proof <index>
+            if let Some(str) = args.get(1) {
+                match str.parse::<usize>() {
+                    Ok(index) => {
+                        let response = tree.generate_proof(index);
+                        for hash in response {
+                            print!("{hash} ");
+                        }
+                        println!();
+                    }
+                    Err(e) => {
+                        return Err(UserInterfaceErrors::NotCorrectTypeError(e));
+                    }
+                }
+            } else {
+                return Err(UserInterfaceErrors::NotEnoughArgumentsError(
+                    "proof <index>".to_string(),
+                ));
+            }
+        }
+        "find" => {
+            // Usage: find hash
+            if let Some(str) = args.get(1) {
+                print_indices(tree.indices_of(str));
+            } else {
+                return Err(UserInterfaceErrors::NotEnoughArgumentsError(
+                    "find hash".to_string(),
+                ));
+            }
+        }
+        "find-unhashed" => {
+            // Usage: find-unhashed unhashed-text
+            if args.len() >= 2 {
+                let text: String = Vec::from(&args[1..]).join(" ");
+                print_indices(tree.indices_of_unhashed(&text));
+            } else {
+                return Err(UserInterfaceErrors::NotEnoughArgumentsError(
+                    "find-unhashed unhashed-text".to_string(),
+                ));
+            }
+        }
+        "print" => {
+            // Usage: print [--width N|full] [--vertical|--horizontal] [--levels N] [--proof index]
+            let options = parse_print_options(&args[1..])?;
+            print!("{}", printer::render(tree, &options));
+        }
+        "root" => match tree.root() {
+            Some(root) => println!("{root}"),
+            None => println!("The tree is empty"),
+        },
+        "export" => {
+            // Usage: export --format dot|json <file> [--proof index]
+            let usage = "export --format dot|json <file> [--proof index]";
+            let (format, file, proof_index) = match &args[1..] {
+                ["--format", format, file] => (*format, *file, None),
+                ["--format", format, file, "--proof", index] => (
+                    *format,
+                    *file,
+                    Some(
+                        index
+                            .parse::<usize>()
+                            .map_err(UserInterfaceErrors::NotCorrectTypeError)?,
+                    ),
+                ),
+                _ => {
+                    return Err(UserInterfaceErrors::NotEnoughArgumentsError(
+                        usage.to_string(),
+                    ));
+                }
+            };
+
+            let exported = match format {
+                "dot" => export::to_dot(tree, proof_index),
+                "json" => export::to_json(tree),
+                other => {
+                    return Err(UserInterfaceErrors::InvalidArgumentError(other.to_string()));
+                }
+            };
+            std::fs::write(file, exported).map_err(UserInterfaceErrors::FileError)?;
+            println!("Tree exported to {file}");
+        }
+        "padding" => {
+            // Usage: padding duplicate-last|zero|promote
+            let padding = match args.get(1) {
+                Some(&"duplicate-last") => Padding::DuplicateLast,
+                Some(&"zero") => Padding::Zero,
+                Some(&"promote") => Padding::Promote,
+                Some(other) => {
+                    return Err(UserInterfaceErrors::InvalidArgumentError(other.to_string()));
+                }
+                None => {
+                    return Err(UserInterfaceErrors::NotEnoughArgumentsError(
+                        "padding duplicate-last|zero|promote".to_string(),
+                    ));
+                }
+            };
+            *tree = std::mem::take(tree).with_padding(padding);
+        }
+        "domain" => {
+            // Usage: domain none|rfc6962|<leaf-tag> <node-tag>
+            let domain = match &args[1..] {
+                ["none"] => DomainSeparation::default(),
+                ["rfc6962"] => DomainSeparation::rfc6962(),
+                [leaf_tag, node_tag] => DomainSeparation::new(*leaf_tag, *node_tag)
+                    .map_err(UserInterfaceErrors::MerkleTreeError)?,
+                _ => {
+                    return Err(UserInterfaceErrors::NotEnoughArgumentsError(
+                        "domain none|rfc6962|<leaf-tag> <node-tag>".to_string(),
+                    ));
+                }
+            };
+            *tree = std::mem::take(tree).with_domain_separation(domain);
+        }
+        "bitcoin-root" => {
+            // Usage: bitcoin-root <txid-1> <txid-2> ... <txid-n>
+            let txids = parse_txids(&args[1..])?;
+            match bitcoin::merkle_root(&txids) {
+                Some(root) => println!("{}", bitcoin::txid_to_hex(&root)),
+                None => {
+                    return Err(UserInterfaceErrors::NotEnoughArgumentsError(
+                        "bitcoin-root <txid-1> <txid-2> ... <txid-n>".to_string(),
+                    ));
+                }
+            }
+        }
+        "bitcoin-proof" => {
+            // Usage: bitcoin-proof index <txid-1> <txid-2> ... <txid-n>
+            if args.len() < 3 {
+                return Err(UserInterfaceErrors::NotEnoughArgumentsError(
+                    "bitcoin-proof index <txid-1> <txid-2> ... <txid-n>".to_string(),
+                ));
+            }
+            let index: usize = args[1]
+                .parse()
+                .map_err(UserInterfaceErrors::NotCorrectTypeError)?;
+            let txids = parse_txids(&args[2..])?;
+            let matches: Vec<bool> = (0..txids.len()).map(|i| i == index).collect();
+
+            let partial_tree = PartialMerkleTree::build(&txids, &matches)
+                .map_err(UserInterfaceErrors::BitcoinError)?;
+            println!("{}", hex::encode(partial_tree.serialize()));
+        }
+        "bitcoin-verify" => {
+            // Usage: bitcoin-verify partial-tree merkle-root
+            if args.len() < 3 {
+                return Err(UserInterfaceErrors::NotEnoughArgumentsError(
+                    "bitcoin-verify partial-tree merkle-root".to_string(),
+                ));
+            }
+            let bytes = hex::decode(args[1]).map_err(|_| {
+                UserInterfaceErrors::BitcoinError(BitcoinErrors::MalformedPartialTreeError(
+                    "not hex encoded".to_string(),
+                ))
+            })?;
+            let expected_root =
+                bitcoin::txid_from_hex(args[2]).map_err(UserInterfaceErrors::BitcoinError)?;
+
+            let (root, matches) = PartialMerkleTree::deserialize(&bytes)
+                .and_then(|partial_tree| partial_tree.extract_matches())
+                .map_err(UserInterfaceErrors::BitcoinError)?;
+            if root == expected_root {
+                println!("Proof has been verified");
+                for (index, txid) in matches {
+                    println!("{index} {}", bitcoin::txid_to_hex(&txid));
+                }
+            } else {
+                println!("Proof has not been verified");
+            }
+        }
+        "keygen" => {
+            // Usage: keygen <key-file> <pubkey-file>
+            let [_, key_file, pubkey_file] = args[..] else {
+                return Err(UserInterfaceErrors::NotEnoughArgumentsError(
+                    "keygen <key-file> <pubkey-file>".to_string(),
+                ));
+            };
+            let key =
+                signed_tree_head::generate_key().map_err(UserInterfaceErrors::SigningError)?;
+            write_private_file(key_file, hex::encode(key.to_bytes()))
+                .map_err(UserInterfaceErrors::FileError)?;
+            std::fs::write(pubkey_file, hex::encode(key.verifying_key().to_bytes()))
+                .map_err(UserInterfaceErrors::FileError)?;
+            println!("Keys written to {key_file} and {pubkey_file}");
+        }
+        "sign-root" => {
+            // Usage: sign-root --key <key-file>
+            let ["sign-root", "--key", key_file] = args[..] else {
+                return Err(UserInterfaceErrors::NotEnoughArgumentsError(
+                    "sign-root --key <key-file>".to_string(),
+                ));
+            };
+            let key = std::fs::read_to_string(key_file).map_err(UserInterfaceErrors::FileError)?;
+            let key = signed_tree_head::signing_key_from_hex(&key)
+                .map_err(UserInterfaceErrors::SigningError)?;
+
+            let head =
+                SignedTreeHead::sign(tree, &key).map_err(UserInterfaceErrors::SigningError)?;
+            println!("{}", head.serialize());
+        }
+        "verify-root" => {
+            // Usage: verify-root --pubkey <pubkey-file> signed-tree-head
+            let ["verify-root", "--pubkey", pubkey_file, serialized] = args[..] else {
+                return Err(UserInterfaceErrors::NotEnoughArgumentsError(
+                    "verify-root --pubkey <pubkey-file> signed-tree-head".to_string(),
+                ));
+            };
+            let pubkey =
+                std::fs::read_to_string(pubkey_file).map_err(UserInterfaceErrors::FileError)?;
+            let pubkey = signed_tree_head::verifying_key_from_hex(&pubkey)
+                .map_err(UserInterfaceErrors::SigningError)?;
+            let head = SignedTreeHead::deserialize(serialized)
+                .map_err(UserInterfaceErrors::SigningError)?;
+
+            if head.verify(&pubkey, tree.padding(), tree.domain_separation()) {
+                println!("Signed tree head has been verified");
+                println!(
+                    "size {}, timestamp {}, root {}",
+                    head.tree_size, head.timestamp, head.root
+                );
+                if tree.root().as_deref() == Some(head.root.as_str()) {
+                    println!("It is the root of the current tree");
+                }
+            } else {
+                println!("Signed tree head has not been verified");
+            }
+        }
+        _ => {
+            println!("Command not recognized, type --help to see the available commands");
+        }
+    }
+    Ok(())
+}
+
+fn parse_print_options(args: &[&str]) -> Result<PrintOptions, UserInterfaceErrors> {
+    let mut options = PrintOptions::default();
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match *arg {
+            "--vertical" => options.layout = Layout::Vertical,
+            "--horizontal" => options.layout = Layout::Horizontal,
+            "--width" | "--levels" | "--proof" => {
+                let Some(value) = args.next() else {
+                    return Err(UserInterfaceErrors::NotEnoughArgumentsError(
+                        "print [--width N|full] [--vertical|--horizontal] [--levels N] [--proof index]"
+                            .to_string(),
+                    ));
+                };
+                if *arg == "--width" && *value == "full" {
+                    options.hash_width = None;
+                    continue;
+                }
+
+                let number: usize = value
+                    .parse()
+                    .map_err(UserInterfaceErrors::NotCorrectTypeError)?;
+                match *arg {
+                    "--width" => options.hash_width = Some(number),
+                    "--levels" => {
+                        let levels = u32::try_from(number).map_err(|_| {
+                            UserInterfaceErrors::InvalidArgumentError(format!("--levels {number}"))
+                        })?;
+                        options.max_levels = Some(levels);
+                    }
+                    _ => options.highlight = Some(number),
+                }
+            }
+            other => return Err(UserInterfaceErrors::InvalidArgumentError(other.to_string())),
+        }
+    }
+
+    Ok(options)
+}
+
+/// Writes a file only its owner can read, for the private key
+fn write_private_file(path: &str, contents: String) -> std::io::Result<()> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+    let mut file = options.open(path)?;
+    // The mode only applies when the file is created, an existing key file keeps its permissions otherwise
+    #[cfg(unix)]
+    file.set_permissions(std::os::unix::fs::PermissionsExt::from_mode(0o600))?;
+    std::io::Write::write_all(&mut file, contents.as_bytes())
+}
+
+fn print_indices(indices: Vec<usize>) {
+    if indices.is_empty() {
+        println!("Leaf not found");
+    } else {
+        for index in indices {
+            print!("{index} ");
+        }
+        println!();
+    }
+}
+
+fn parse_txids(args: &[&str]) -> Result<Vec<[u8; 32]>, UserInterfaceErrors> {
+    args.iter()
+        .map(|txid| bitcoin::txid_from_hex(txid).map_err(UserInterfaceErrors::BitcoinError))
+        .collect()
+}
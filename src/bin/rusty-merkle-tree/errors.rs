@@ -0,0 +1,16 @@
+use std::io;
+use std::num::ParseIntError;
+
+use rusty_merkle_tree::errors::{BitcoinErrors, MerkleTreeErrors, SigningErrors};
+
+/// Errors of the commands, printed to the user by the REPL
+#[allow(clippy::enum_variant_names)]
+pub enum UserInterfaceErrors {
+    NotEnoughArgumentsError(String),
+    NotCorrectTypeError(ParseIntError),
+    InvalidArgumentError(String),
+    FileError(io::Error),
+    MerkleTreeError(MerkleTreeErrors),
+    BitcoinError(BitcoinErrors),
+    SigningError(SigningErrors),
+}
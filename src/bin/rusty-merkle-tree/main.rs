@@ -0,0 +1,46 @@
+mod commands;
+mod errors;
+
+use rusty_merkle_tree::merkle_tree::MerkleTree;
+
+use crate::commands::process_comands;
+use crate::errors::UserInterfaceErrors;
+
+fn main() {
+    println!();
+    println!("Welcome to this Merkle Tree simulator. Type --help to list the available commands");
+    let mut tree = MerkleTree::new().with_leaf_index();
+    loop {
+        println!();
+
+        let mut input_line = String::new();
+        if let Ok(bytes_read) = std::io::stdin().read_line(&mut input_line) {
+            if bytes_read <= 1 {
+                return;
+            }
+        } else {
+            println!("Could not receive from stdin");
+            return;
+        }
+
+        let response = process_comands(input_line, &mut tree);
+        if let Err(UserInterfaceErrors::NotCorrectTypeError(e)) = response {
+            println!("{:?}", e);
+        } else if let Err(UserInterfaceErrors::NotEnoughArgumentsError(usage)) = response {
+            println!(
+                "The amount of arguments is not the expected, usage: {}",
+                usage
+            );
+        } else if let Err(UserInterfaceErrors::InvalidArgumentError(argument)) = response {
+            println!("Invalid argument: {}", argument);
+        } else if let Err(UserInterfaceErrors::FileError(e)) = response {
+            println!("{}", e);
+        } else if let Err(UserInterfaceErrors::MerkleTreeError(e)) = response {
+            println!("{}", e);
+        } else if let Err(UserInterfaceErrors::BitcoinError(e)) = response {
+            println!("{}", e);
+        } else if let Err(UserInterfaceErrors::SigningError(e)) = response {
+            println!("{}", e);
+        }
+    }
+}
@@ -0,0 +1,195 @@
+use std::io::Read;
+use std::sync::Arc;
+use std::thread;
+
+use rusty_merkle_tree::hashing;
+use rusty_merkle_tree::merkle_tree::MerkleTree;
+use rusty_merkle_tree::shared::SharedMerkleTree;
+use serde_json::{json, Value};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+const DEFAULT_ADDRESS: &str = "127.0.0.1:8080";
+const WORKERS: usize = 4;
+/// Bytes a request body can have, so a client can't make the server hold an endless body in memory
+const MAX_BODY: u64 = 1 << 20;
+
+/// Usage: server [--address host:port] [leaves-file]
+/// The leaves file has a hash per line. With port 0 a free one is chosen, and the address is printed on start
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (address, leaves_file) = match &args[..] {
+        [] => (DEFAULT_ADDRESS, None),
+        [file] => (DEFAULT_ADDRESS, Some(file)),
+        [flag, address] if flag == "--address" => (address.as_str(), None),
+        [flag, address, file] if flag == "--address" => (address.as_str(), Some(file)),
+        _ => {
+            eprintln!("Usage: server [--address host:port] [leaves-file]");
+            std::process::exit(2);
+        }
+    };
+
+    let mut tree = MerkleTree::new().with_leaf_index();
+    if let Some(file) = leaves_file {
+        match std::fs::read_to_string(file) {
+            Ok(leaves) => {
+                let leaves = leaves
+                    .lines()
+                    .map(str::trim)
+                    .filter(|l| !l.is_empty())
+                    .map(str::to_string)
+                    .collect();
+                tree = tree.with_leaves(leaves);
+            }
+            Err(e) => {
+                eprintln!("Could not read {file}: {e}");
+                std::process::exit(1);
+            }
+        }
+    }
+
+    let server = match Server::http(address) {
+        Ok(server) => Arc::new(server),
+        Err(e) => {
+            eprintln!("Could not listen on {address}: {e}");
+            std::process::exit(1);
+        }
+    };
+    println!("Listening on http://{}", server.server_addr());
+
+    // Readers don't block each other, so the requests are answered by a few threads sharing the tree
+    let tree = SharedMerkleTree::new(tree);
+    let workers: Vec<_> = (0..WORKERS)
+        .map(|_| {
+            let server = Arc::clone(&server);
+            let tree = tree.clone();
+            thread::spawn(move || {
+                for request in server.incoming_requests() {
+                    respond(&tree, request);
+                }
+            })
+        })
+        .collect();
+    for worker in workers {
+        let _ = worker.join();
+    }
+}
+
+fn respond(tree: &SharedMerkleTree, mut request: Request) {
+    // One byte more than the limit is read to tell a body of the limit from a longer one
+    let mut body = String::new();
+    let read = request
+        .as_reader()
+        .take(MAX_BODY + 1)
+        .read_to_string(&mut body);
+    let (status, json) = match read {
+        Ok(_) if body.len() as u64 > MAX_BODY => error(413, "the body is too large"),
+        Ok(_) => handle(tree, request.method(), request.url(), &body),
+        Err(_) => error(400, "the body is not valid text"),
+    };
+
+    let content_type =
+        Header::from_bytes("Content-Type", "application/json").expect("the header is valid");
+    let response = Response::from_string(json.to_string())
+        .with_status_code(status)
+        .with_header(content_type);
+    let _ = request.respond(response);
+}
+
+/// GET /root, GET /proof/{index}, GET /leaf/{index}, POST /leaves and POST /verify
+fn handle(tree: &SharedMerkleTree, method: &Method, url: &str, body: &str) -> (u16, Value) {
+    let path = url.split('?').next().unwrap_or_default();
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+
+    match (method, &segments[..]) {
+        (Method::Get, ["root"]) => (200, json!({ "size": tree.len(), "root": tree.root() })),
+        (Method::Get, ["proof", index]) => {
+            let Ok(index) = index.parse::<usize>() else {
+                return error(400, "the index is not a number");
+            };
+            match tree.read(|tree| Some((tree.prove(index)?, tree.root()?.to_string()))) {
+                Some((proof, root)) => (
+                    200,
+                    json!({ "index": index, "leaf": proof.leaf, "proof": proof.siblings, "root": root }),
+                ),
+                None => error(404, "there is no leaf at the index"),
+            }
+        }
+        (Method::Get, ["leaf", index]) => {
+            let Ok(index) = index.parse::<usize>() else {
+                return error(400, "the index is not a number");
+            };
+            match tree.read(|tree| tree.leaf(index).map(str::to_string)) {
+                Some(leaf) => (200, json!({ "index": index, "leaf": leaf })),
+                None => error(404, "there is no leaf at the index"),
+            }
+        }
+        (Method::Post, ["leaves"]) => append(tree, body),
+        (Method::Post, ["verify"]) => verify(tree, body),
+        (_, ["root" | "proof" | "leaf" | "leaves" | "verify", ..]) => {
+            error(405, "method not allowed")
+        }
+        _ => error(404, "not found"),
+    }
+}
+
+/// Body: {"leaves": [hash, ...]} or {"unhashed": [text, ...]}. All of them are added at once
+fn append(tree: &SharedMerkleTree, body: &str) -> (u16, Value) {
+    let Ok(body) = serde_json::from_str::<Value>(body) else {
+        return error(400, "the body is not valid JSON");
+    };
+    let (values, unhashed) = match (body.get("leaves"), body.get("unhashed")) {
+        (Some(leaves), None) => (leaves, false),
+        (None, Some(texts)) => (texts, true),
+        _ => return error(400, "expected a \"leaves\" or an \"unhashed\" list"),
+    };
+    let Some(values) = values
+        .as_array()
+        .and_then(|values| values.iter().map(Value::as_str).collect::<Option<Vec<_>>>())
+    else {
+        return error(400, "expected a list of strings");
+    };
+
+    // The texts are hashed before taking the lock, so the readers only wait for the leaves to be placed
+    let leaves: Vec<String> = if unhashed {
+        let domain = tree.read(|tree| tree.domain_separation().clone());
+        values
+            .into_iter()
+            .map(|text| hashing::hash_leaf(&domain, text.as_bytes()))
+            .collect()
+    } else {
+        values.into_iter().map(str::to_string).collect()
+    };
+
+    let (first_index, size, root) = tree.write(|tree| {
+        let first_index = tree.len();
+        tree.extend(leaves);
+        (first_index, tree.len(), tree.root())
+    });
+    (
+        200,
+        json!({ "first_index": first_index, "size": size, "root": root }),
+    )
+}
+
+/// Body: {"proof": [hash, ...], "leaf": hash, "index": index}
+fn verify(tree: &SharedMerkleTree, body: &str) -> (u16, Value) {
+    let Ok(body) = serde_json::from_str::<Value>(body) else {
+        return error(400, "the body is not valid JSON");
+    };
+    let proof = body["proof"]
+        .as_array()
+        .and_then(|proof| proof.iter().map(Value::as_str).collect::<Option<Vec<_>>>());
+    let (Some(proof), Some(leaf), Some(index)) =
+        (proof, body["leaf"].as_str(), body["index"].as_u64())
+    else {
+        return error(400, "expected \"proof\", \"leaf\" and \"index\"");
+    };
+
+    let proof = proof.into_iter().map(str::to_string).collect();
+    let valid = tree.verify(proof, leaf.to_string(), index as usize);
+    (200, json!({ "valid": valid }))
+}
+
+fn error(status: u16, message: &str) -> (u16, Value) {
+    (status, json!({ "error": message }))
+}
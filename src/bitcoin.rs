@@ -0,0 +1,519 @@
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+
+use sha2::{Digest, Sha256};
+
+use crate::errors::BitcoinErrors;
+
+/// Size in bytes of a serialized block header
+const HEADER_SIZE: usize = 80;
+
+/// Same bound Bitcoin Core uses: a transaction can't be smaller than 60 bytes, so a 1MB block can't hold more than this
+const MAX_TRANSACTIONS: u32 = 1_000_000 / 60;
+
+/// Bitcoin hashes everything twice with SHA256 (SHA256d)
+pub fn double_sha256(data: &[u8]) -> [u8; 32] {
+    let first: [u8; 32] = Sha256::digest(data).into();
+    Sha256::digest(first).into()
+}
+
+/// Unlike the MerkleTree, here the raw 32 bytes are hashed, not their hex representation
+fn combine_hashes(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    let mut concatenated = [0u8; 64];
+    concatenated[..32].copy_from_slice(left);
+    concatenated[32..].copy_from_slice(right);
+    double_sha256(&concatenated)
+}
+
+/// Txids are displayed (in explorers and RPC) in reverse byte order, so this turns them into the internal little-endian bytes
+pub fn txid_from_hex(txid: &str) -> Result<[u8; 32], BitcoinErrors> {
+    let mut bytes: [u8; 32] = hex::decode(txid)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| BitcoinErrors::InvalidTxidError(txid.to_string()))?;
+    bytes.reverse();
+    Ok(bytes)
+}
+
+/// Inverse of txid_from_hex, gives back the hash in display order
+pub fn txid_to_hex(txid: &[u8; 32]) -> String {
+    let mut reversed = *txid;
+    reversed.reverse();
+    hex::encode(reversed)
+}
+
+/// The merkle root of a block. Each level with an odd amount of nodes duplicates its last one, which is
+/// not the same as filling the leaves up to a power of two. Returns None when there are no transactions
+pub fn merkle_root(txids: &[[u8; 32]]) -> Option<[u8; 32]> {
+    if txids.is_empty() {
+        return None;
+    }
+
+    let mut level = txids.to_vec();
+    while level.len() > 1 {
+        level = level
+            .chunks(2)
+            .map(|pair| combine_hashes(&pair[0], pair.last().unwrap_or(&pair[0])))
+            .collect();
+    }
+
+    Some(level[0])
+}
+
+/// Double hash of the header, the block hash is usually displayed reversed (see txid_to_hex)
+pub fn block_hash(header: &[u8]) -> Result<[u8; 32], BitcoinErrors> {
+    if header.len() != HEADER_SIZE {
+        return Err(BitcoinErrors::InvalidHeaderError(header.len()));
+    }
+    Ok(double_sha256(header))
+}
+
+/// The merkle root is stored in the header right after the version (4 bytes) and the previous block hash (32 bytes)
+pub fn header_merkle_root(header: &[u8]) -> Result<[u8; 32], BitcoinErrors> {
+    if header.len() != HEADER_SIZE {
+        return Err(BitcoinErrors::InvalidHeaderError(header.len()));
+    }
+    let mut root = [0u8; 32];
+    root.copy_from_slice(&header[36..68]);
+    Ok(root)
+}
+
+/// The partial merkle tree sent in a `merkleblock` message (BIP 37). It carries the hashes and the flag bits
+/// needed to rebuild the merkle root from a subset of matched transactions
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PartialMerkleTree {
+    total_transactions: u32,
+    hashes: Vec<[u8; 32]>,
+    /// One bit per visited node, in depth-first order. Packed when serializing
+    flags: Vec<bool>,
+}
+
+impl PartialMerkleTree {
+    /// Builds the partial tree for the transactions whose flag in matches is true
+    pub fn build(txids: &[[u8; 32]], matches: &[bool]) -> Result<Self, BitcoinErrors> {
+        if txids.is_empty() || txids.len() != matches.len() {
+            return Err(BitcoinErrors::MalformedPartialTreeError(
+                "there must be one match flag per transaction".to_string(),
+            ));
+        }
+        if txids.len() > MAX_TRANSACTIONS as usize {
+            return Err(BitcoinErrors::MalformedPartialTreeError(
+                "too many transactions".to_string(),
+            ));
+        }
+
+        let mut tree = PartialMerkleTree {
+            total_transactions: txids.len() as u32,
+            hashes: Vec::new(),
+            flags: Vec::new(),
+        };
+        let height = tree.height();
+        tree.traverse_and_build(height, 0, txids, matches);
+
+        Ok(tree)
+    }
+
+    pub fn total_transactions(&self) -> u32 {
+        self.total_transactions
+    }
+
+    /// The height at which the tree has a single node, the root
+    fn height(&self) -> u32 {
+        let mut height = 0;
+        while self.width(height) > 1 {
+            height += 1;
+        }
+        height
+    }
+
+    /// Amount of nodes at a given height, 0 being the level of the transactions
+    fn width(&self, height: u32) -> usize {
+        let total = self.total_transactions as usize;
+        (total + (1 << height) - 1) >> height
+    }
+
+    /// Hash of the node at (height, pos) calculated from the transactions
+    fn calculate_hash(&self, height: u32, pos: usize, txids: &[[u8; 32]]) -> [u8; 32] {
+        if height == 0 {
+            return txids[pos];
+        }
+
+        let left = self.calculate_hash(height - 1, pos * 2, txids);
+        // If there's no right sibling, the left one is duplicated
+        let right = if pos * 2 + 1 < self.width(height - 1) {
+            self.calculate_hash(height - 1, pos * 2 + 1, txids)
+        } else {
+            left
+        };
+        combine_hashes(&left, &right)
+    }
+
+    /// Descends only into subtrees that contain a match, the rest are sent as a single hash
+    fn traverse_and_build(
+        &mut self,
+        height: u32,
+        pos: usize,
+        txids: &[[u8; 32]],
+        matches: &[bool],
+    ) {
+        let begin = pos << height;
+        let end = ((pos + 1) << height).min(txids.len());
+        let parent_of_match = matches[begin..end].iter().any(|matched| *matched);
+
+        self.flags.push(parent_of_match);
+
+        if height == 0 || !parent_of_match {
+            let hash = self.calculate_hash(height, pos, txids);
+            self.hashes.push(hash);
+        } else {
+            self.traverse_and_build(height - 1, pos * 2, txids, matches);
+            if pos * 2 + 1 < self.width(height - 1) {
+                self.traverse_and_build(height - 1, pos * 2 + 1, txids, matches);
+            }
+        }
+    }
+
+    /// Rebuilds the merkle root and returns it with the matched (index, txid) pairs. The root has to be compared
+    /// against the one in the block header to know that the transactions are in the block
+    #[allow(clippy::type_complexity)]
+    pub fn extract_matches(&self) -> Result<([u8; 32], Vec<(usize, [u8; 32])>), BitcoinErrors> {
+        if self.total_transactions == 0 || self.total_transactions > MAX_TRANSACTIONS {
+            return Err(BitcoinErrors::MalformedPartialTreeError(
+                "invalid amount of transactions".to_string(),
+            ));
+        }
+        // There can't be more hashes provided than transactions, nor less flag bits than hashes
+        if self.hashes.len() > self.total_transactions as usize
+            || self.flags.len() < self.hashes.len()
+        {
+            return Err(BitcoinErrors::MalformedPartialTreeError(
+                "inconsistent amount of hashes and flags".to_string(),
+            ));
+        }
+
+        let mut extraction = Extraction {
+            bits_used: 0,
+            hashes_used: 0,
+            matches: Vec::new(),
+        };
+        let root = self.traverse_and_extract(self.height(), 0, &mut extraction)?;
+
+        // Everything sent has to be consumed (the flags up to the byte they were packed in)
+        if extraction.bits_used.div_ceil(8) != self.flags.len().div_ceil(8)
+            || extraction.hashes_used != self.hashes.len()
+        {
+            return Err(BitcoinErrors::MalformedPartialTreeError(
+                "not all hashes or flags were used".to_string(),
+            ));
+        }
+
+        Ok((root, extraction.matches))
+    }
+
+    fn traverse_and_extract(
+        &self,
+        height: u32,
+        pos: usize,
+        extraction: &mut Extraction,
+    ) -> Result<[u8; 32], BitcoinErrors> {
+        let parent_of_match = *self.flags.get(extraction.bits_used).ok_or_else(|| {
+            BitcoinErrors::MalformedPartialTreeError("ran out of flag bits".to_string())
+        })?;
+        extraction.bits_used += 1;
+
+        if height == 0 || !parent_of_match {
+            let hash = *self.hashes.get(extraction.hashes_used).ok_or_else(|| {
+                BitcoinErrors::MalformedPartialTreeError("ran out of hashes".to_string())
+            })?;
+            extraction.hashes_used += 1;
+
+            if height == 0 && parent_of_match {
+                extraction.matches.push((pos, hash));
+            }
+            return Ok(hash);
+        }
+
+        let left = self.traverse_and_extract(height - 1, pos * 2, extraction)?;
+        let right = if pos * 2 + 1 < self.width(height - 1) {
+            let right = self.traverse_and_extract(height - 1, pos * 2 + 1, extraction)?;
+            // Two equal children would allow the CVE-2012-2459 duplication trick, a real tree never has them
+            if right == left {
+                return Err(BitcoinErrors::MalformedPartialTreeError(
+                    "duplicated node".to_string(),
+                ));
+            }
+            right
+        } else {
+            left
+        };
+
+        Ok(combine_hashes(&left, &right))
+    }
+
+    /// Serializes as in the merkleblock message: transaction count, hashes and flag bytes (least significant bit first)
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&self.total_transactions.to_le_bytes());
+
+        write_compact_size(&mut bytes, self.hashes.len() as u64);
+        for hash in &self.hashes {
+            bytes.extend_from_slice(hash);
+        }
+
+        let mut flag_bytes = vec![0u8; self.flags.len().div_ceil(8)];
+        for (i, flag) in self.flags.iter().enumerate() {
+            if *flag {
+                flag_bytes[i / 8] |= 1 << (i % 8);
+            }
+        }
+        write_compact_size(&mut bytes, flag_bytes.len() as u64);
+        bytes.extend_from_slice(&flag_bytes);
+
+        bytes
+    }
+
+    pub fn deserialize(bytes: &[u8]) -> Result<Self, BitcoinErrors> {
+        let mut reader = ByteReader { bytes, pos: 0 };
+
+        let total_transactions = u32::from_le_bytes(reader.read_array()?);
+
+        let hash_count = reader.read_compact_size()?;
+        if hash_count > MAX_TRANSACTIONS as u64 {
+            return Err(BitcoinErrors::MalformedPartialTreeError(
+                "too many hashes".to_string(),
+            ));
+        }
+        let mut hashes = Vec::with_capacity(hash_count as usize);
+        for _ in 0..hash_count {
+            hashes.push(reader.read_array()?);
+        }
+
+        let flag_byte_count = reader.read_compact_size()? as usize;
+        let flag_bytes = reader.read_slice(flag_byte_count)?;
+        let flags = (0..flag_byte_count * 8)
+            .map(|i| flag_bytes[i / 8] & (1 << (i % 8)) != 0)
+            .collect();
+
+        if reader.pos != bytes.len() {
+            return Err(BitcoinErrors::MalformedPartialTreeError(
+                "trailing bytes".to_string(),
+            ));
+        }
+
+        Ok(PartialMerkleTree {
+            total_transactions,
+            hashes,
+            flags,
+        })
+    }
+}
+
+/// State shared along the traversal when extracting the matches
+struct Extraction {
+    bits_used: usize,
+    hashes_used: usize,
+    matches: Vec<(usize, [u8; 32])>,
+}
+
+fn write_compact_size(bytes: &mut Vec<u8>, value: u64) {
+    match value {
+        0..=0xfc => bytes.push(value as u8),
+        0xfd..=0xffff => {
+            bytes.push(0xfd);
+            bytes.extend_from_slice(&(value as u16).to_le_bytes());
+        }
+        0x10000..=0xffff_ffff => {
+            bytes.push(0xfe);
+            bytes.extend_from_slice(&(value as u32).to_le_bytes());
+        }
+        _ => {
+            bytes.push(0xff);
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+}
+
+struct ByteReader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    fn read_slice(&mut self, len: usize) -> Result<&'a [u8], BitcoinErrors> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|end| *end <= self.bytes.len());
+        match end {
+            Some(end) => {
+                let slice = &self.bytes[self.pos..end];
+                self.pos = end;
+                Ok(slice)
+            }
+            None => Err(BitcoinErrors::MalformedPartialTreeError(
+                "unexpected end of data".to_string(),
+            )),
+        }
+    }
+
+    fn read_array<const N: usize>(&mut self) -> Result<[u8; N], BitcoinErrors> {
+        let mut array = [0u8; N];
+        array.copy_from_slice(self.read_slice(N)?);
+        Ok(array)
+    }
+
+    fn read_compact_size(&mut self) -> Result<u64, BitcoinErrors> {
+        let value = match self.read_array::<1>()?[0] {
+            0xfd => u16::from_le_bytes(self.read_array()?) as u64,
+            0xfe => u32::from_le_bytes(self.read_array()?) as u64,
+            0xff => u64::from_le_bytes(self.read_array()?),
+            small => small as u64,
+        };
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GENESIS_HEADER: &str = "0100000000000000000000000000000000000000000000000000000000000000000000003ba3edfd7a7b12b27ac72c3e67768f617fc81bc3888a51323a9fb8aa4b1e5e4a29ab5f49ffff001d1dac2b7c";
+
+    const BLOCK_100000_TXIDS: [&str; 4] = [
+        "8c14f0db3df150123e6f3dbbf30f8b955a8249b62ac1d1ff16284aefa3d06d87",
+        "fff2525b8931402dd09222c50775608f75787bd2b87e56995a7bdd30f79702c4",
+        "6359f0868171b1d194cbee1af2f16ea598ae8fad666d9b012c8ed2b79a236ec4",
+        "e9a66845e05d5abc0ad04ec80f774a7e585c6e8db975962d069a522137b80c1d",
+    ];
+
+    fn txids(hexes: &[&str]) -> Vec<[u8; 32]> {
+        hexes
+            .iter()
+            .map(|txid| txid_from_hex(txid).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_01_genesis_header_hash_and_root_are_the_known_ones() {
+        // The genesis block has a single transaction, so its txid is the merkle root
+        let header = hex::decode(GENESIS_HEADER).unwrap();
+
+        assert_eq!(
+            "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f",
+            txid_to_hex(&block_hash(&header).unwrap())
+        );
+
+        let coinbase = txids(&["4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b"]);
+        assert_eq!(
+            header_merkle_root(&header).unwrap(),
+            merkle_root(&coinbase).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_02_merkle_root_of_block_170_is_the_known_one() {
+        let block = txids(&[
+            "b1fea52486ce0c62bb442b530a3f0132b826c74e473d1f2c220bfa78111c5082",
+            "f4184fc596403b9d638783cf57adfe4c75c605f6356fbc91338530e9831e9e16",
+        ]);
+
+        assert_eq!(
+            "7dac2c5666815c17a3b36427de37bb9d2e2c5ccec3f8633eb91a4205cb4c10ff",
+            txid_to_hex(&merkle_root(&block).unwrap())
+        );
+    }
+
+    #[test]
+    fn test_03_merkle_root_of_block_100000_is_the_known_one() {
+        assert_eq!(
+            "f3e94742aca4b5ef85488dc37c06c3282295ffec960994b2c0d5ac2a25a95766",
+            txid_to_hex(&merkle_root(&txids(&BLOCK_100000_TXIDS)).unwrap())
+        );
+    }
+
+    #[test]
+    fn test_04_odd_levels_duplicate_their_last_node() {
+        // With three transactions the third one is hashed with itself, and then [a, b, c] shares root with [a, b, c, c]
+        let three = txids(&BLOCK_100000_TXIDS[..3]);
+        let mut four = three.clone();
+        four.push(three[2]);
+
+        let left = combine_hashes(&three[0], &three[1]);
+        let right = combine_hashes(&three[2], &three[2]);
+
+        assert_eq!(combine_hashes(&left, &right), merkle_root(&three).unwrap());
+        assert_eq!(merkle_root(&four), merkle_root(&three));
+        assert_eq!(None, merkle_root(&[]));
+    }
+
+    #[test]
+    fn test_05_partial_tree_extracts_the_matched_transactions() {
+        let block = txids(&BLOCK_100000_TXIDS);
+        let tree = PartialMerkleTree::build(&block, &[false, true, false, true]).unwrap();
+
+        let (root, matches) = tree.extract_matches().unwrap();
+
+        assert_eq!(merkle_root(&block).unwrap(), root);
+        assert_eq!(vec![(1, block[1]), (3, block[3])], matches);
+    }
+
+    #[test]
+    fn test_06_partial_tree_survives_serialization() {
+        let mut block = txids(&BLOCK_100000_TXIDS);
+        block.push(double_sha256(b"fifth"));
+        let tree = PartialMerkleTree::build(&block, &[false, false, false, false, true]).unwrap();
+
+        let bytes = tree.serialize();
+        let deserialized = PartialMerkleTree::deserialize(&bytes).unwrap();
+        let (root, matches) = deserialized.extract_matches().unwrap();
+
+        assert_eq!(5, deserialized.total_transactions());
+        assert_eq!(merkle_root(&block).unwrap(), root);
+        assert_eq!(vec![(4, block[4])], matches);
+    }
+
+    #[test]
+    fn test_07_tampered_partial_tree_doesnt_give_the_root() {
+        let block = txids(&BLOCK_100000_TXIDS);
+        let tree = PartialMerkleTree::build(&block, &[true, false, false, false]).unwrap();
+        let mut bytes = tree.serialize();
+
+        // Flip a bit of the first hash
+        bytes[5] ^= 1;
+        let tampered = PartialMerkleTree::deserialize(&bytes).unwrap();
+
+        let (root, _) = tampered.extract_matches().unwrap();
+        assert_ne!(merkle_root(&block).unwrap(), root);
+    }
+
+    #[test]
+    fn test_08_truncated_or_inconsistent_partial_trees_are_rejected() {
+        let block = txids(&BLOCK_100000_TXIDS);
+        let bytes = PartialMerkleTree::build(&block, &[true, true, true, true])
+            .unwrap()
+            .serialize();
+
+        assert!(PartialMerkleTree::deserialize(&bytes[..bytes.len() - 1]).is_err());
+
+        let mut missing_hash = PartialMerkleTree::deserialize(&bytes).unwrap();
+        missing_hash.hashes.pop();
+        assert!(missing_hash.extract_matches().is_err());
+    }
+
+    #[test]
+    fn test_09_duplicated_subtrees_are_rejected() {
+        // [a, b, c, c] has the same root as [a, b, c], but a proof for it must not be accepted
+        let mut block = txids(&BLOCK_100000_TXIDS[..3]);
+        block.push(block[2]);
+        let tree = PartialMerkleTree::build(&block, &[false, false, true, true]).unwrap();
+
+        assert!(tree.extract_matches().is_err());
+    }
+
+    #[test]
+    fn test_10_txids_must_be_32_bytes_of_hex() {
+        assert!(txid_from_hex("not a txid").is_err());
+        assert!(txid_from_hex("abcd").is_err());
+    }
+}
@@ -0,0 +1,244 @@
+use std::ffi::{c_char, CStr};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+
+use crate::merkle_tree::MerkleTree;
+
+/// Size of a hash as a C string: 64 hex characters and the NUL. Proofs are written as consecutive hashes of this size
+pub const MT_HASH_SIZE: usize = 65;
+
+/// What every function returns. Nothing panics through the C boundary, a panic inside the tree becomes `Panic`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MtStatus {
+    Ok = 0,
+    NullPointer = 1,
+    InvalidUtf8 = 2,
+    /// The hash is not 64 hex characters
+    InvalidHash = 3,
+    IndexOutOfRange = 4,
+    EmptyTree = 5,
+    /// The amount of hashes needed is still written, so the call can be repeated with a bigger buffer
+    BufferTooSmall = 6,
+    Panic = 7,
+}
+
+/// Opaque handle to a tree, created by mt_new and released by mt_free
+pub struct MtTree {
+    tree: MerkleTree,
+}
+
+/// A new empty tree, with the default padding and without domain separation. Null only if it could not be created
+#[no_mangle]
+pub extern "C" fn mt_new() -> *mut MtTree {
+    panic::catch_unwind(|| MtTree {
+        tree: MerkleTree::new(),
+    })
+    .map(|tree| Box::into_raw(Box::new(tree)))
+    .unwrap_or(ptr::null_mut())
+}
+
+/// Releases the tree. Null is ignored
+///
+/// # Safety
+/// The tree must come from mt_new and not have been released before
+#[no_mangle]
+pub unsafe extern "C" fn mt_free(tree: *mut MtTree) {
+    if !tree.is_null() {
+        drop(Box::from_raw(tree));
+    }
+}
+
+/// Adds an already hashed leaf, 64 hex characters
+///
+/// # Safety
+/// The tree must come from mt_new and the hash must be a NUL terminated string
+#[no_mangle]
+pub unsafe extern "C" fn mt_add(tree: *mut MtTree, hash: *const c_char) -> MtStatus {
+    guard(|| {
+        let tree = tree_mut(tree)?;
+        let hash = read_hash(hash)?;
+        tree.tree.add(hash);
+        Ok(())
+    })
+}
+
+/// Hashes the text and adds it as a leaf
+///
+/// # Safety
+/// The tree must come from mt_new and the text must be a NUL terminated string
+#[no_mangle]
+pub unsafe extern "C" fn mt_add_unhashed(tree: *mut MtTree, text: *const c_char) -> MtStatus {
+    guard(|| {
+        let tree = tree_mut(tree)?;
+        let text = read_str(text)?;
+        tree.tree.add_unhashed(text.to_string());
+        Ok(())
+    })
+}
+
+/// Writes the root, as a NUL terminated hex string, to out
+///
+/// # Safety
+/// The tree must come from mt_new and out must have room for MT_HASH_SIZE characters
+#[no_mangle]
+pub unsafe extern "C" fn mt_root(tree: *const MtTree, out: *mut c_char) -> MtStatus {
+    guard(|| {
+        let tree = tree_ref(tree)?;
+        if out.is_null() {
+            return Err(MtStatus::NullPointer);
+        }
+        let root = tree.tree.root().ok_or(MtStatus::EmptyTree)?;
+        write_hash(&root, out);
+        Ok(())
+    })
+}
+
+/// Writes the proof of the leaf at the index to out, as proof_len consecutive hashes of MT_HASH_SIZE characters. If
+/// capacity (in hashes) is not enough, only proof_len is written. Passing a capacity of 0 asks for the size
+///
+/// # Safety
+/// The tree must come from mt_new, out must have room for capacity hashes and proof_len must be writable
+#[no_mangle]
+pub unsafe extern "C" fn mt_proof(
+    tree: *const MtTree,
+    index: usize,
+    out: *mut c_char,
+    capacity: usize,
+    proof_len: *mut usize,
+) -> MtStatus {
+    guard(|| {
+        let tree = tree_ref(tree)?;
+        if proof_len.is_null() {
+            return Err(MtStatus::NullPointer);
+        }
+        let proof = tree.tree.prove(index).ok_or(MtStatus::IndexOutOfRange)?;
+
+        *proof_len = proof.siblings.len();
+        if proof.siblings.len() > capacity {
+            return Err(MtStatus::BufferTooSmall);
+        }
+        if out.is_null() && !proof.siblings.is_empty() {
+            return Err(MtStatus::NullPointer);
+        }
+        for (i, sibling) in proof.siblings.iter().enumerate() {
+            write_hash(sibling, out.add(i * MT_HASH_SIZE));
+        }
+        Ok(())
+    })
+}
+
+/// Checks a proof in the layout mt_proof writes. valid is set to false for proofs of another leaf, index or tree
+///
+/// # Safety
+/// The tree must come from mt_new, proof must hold proof_len hashes of MT_HASH_SIZE characters, the leaf must be a
+/// NUL terminated string and valid must be writable
+#[no_mangle]
+pub unsafe extern "C" fn mt_verify(
+    tree: *const MtTree,
+    proof: *const c_char,
+    proof_len: usize,
+    leaf: *const c_char,
+    index: usize,
+    valid: *mut bool,
+) -> MtStatus {
+    guard(|| {
+        let tree = tree_ref(tree)?;
+        if valid.is_null() || (proof.is_null() && proof_len > 0) {
+            return Err(MtStatus::NullPointer);
+        }
+        let leaf = read_hash(leaf)?;
+        let proof = (0..proof_len)
+            .map(|i| read_hash(proof.add(i * MT_HASH_SIZE)))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        *valid = tree.tree.verify(proof, leaf, index);
+        Ok(())
+    })
+}
+
+/// Runs the body of a function, turning a panic into a status
+fn guard(body: impl FnOnce() -> Result<(), MtStatus>) -> MtStatus {
+    match panic::catch_unwind(AssertUnwindSafe(body)) {
+        Ok(Ok(())) => MtStatus::Ok,
+        Ok(Err(status)) => status,
+        Err(_) => MtStatus::Panic,
+    }
+}
+
+unsafe fn tree_ref<'a>(tree: *const MtTree) -> Result<&'a MtTree, MtStatus> {
+    tree.as_ref().ok_or(MtStatus::NullPointer)
+}
+
+unsafe fn tree_mut<'a>(tree: *mut MtTree) -> Result<&'a mut MtTree, MtStatus> {
+    tree.as_mut().ok_or(MtStatus::NullPointer)
+}
+
+unsafe fn read_str<'a>(string: *const c_char) -> Result<&'a str, MtStatus> {
+    if string.is_null() {
+        return Err(MtStatus::NullPointer);
+    }
+    CStr::from_ptr(string)
+        .to_str()
+        .map_err(|_| MtStatus::InvalidUtf8)
+}
+
+unsafe fn read_hash(hash: *const c_char) -> Result<String, MtStatus> {
+    let hash = read_str(hash)?;
+    if hash.len() != MT_HASH_SIZE - 1 || !hash.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(MtStatus::InvalidHash);
+    }
+    Ok(hash.to_string())
+}
+
+/// The nodes of the tree are always hashes, so they fit in MT_HASH_SIZE with the NUL
+unsafe fn write_hash(hash: &str, out: *mut c_char) {
+    let len = hash.len().min(MT_HASH_SIZE - 1);
+    ptr::copy_nonoverlapping(hash.as_ptr().cast::<c_char>(), out, len);
+    *out.add(len) = 0;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::{c_char, CStr, CString};
+    use std::ptr;
+
+    use super::*;
+
+    const HASH_A: &str = "ca978112ca1bbdcafac231b39a23dc4da786eff8147c4e72b9807785afee48bb";
+
+    #[test]
+    fn test_01_invalid_arguments_return_error_codes() {
+        let hash = CString::new(HASH_A).unwrap();
+        let short = CString::new("ca97").unwrap();
+        let mut root = [0 as c_char; MT_HASH_SIZE];
+        unsafe {
+            assert_eq!(
+                MtStatus::NullPointer,
+                mt_add(ptr::null_mut(), hash.as_ptr())
+            );
+
+            let tree = mt_new();
+            assert_eq!(MtStatus::EmptyTree, mt_root(tree, root.as_mut_ptr()));
+            assert_eq!(MtStatus::InvalidHash, mt_add(tree, short.as_ptr()));
+            assert_eq!(MtStatus::NullPointer, mt_add(tree, ptr::null()));
+            assert_eq!(MtStatus::Ok, mt_add(tree, hash.as_ptr()));
+
+            let mut proof_len = 0;
+            assert_eq!(
+                MtStatus::IndexOutOfRange,
+                mt_proof(tree, 1, ptr::null_mut(), 0, &mut proof_len)
+            );
+            assert_eq!(
+                MtStatus::BufferTooSmall,
+                mt_proof(tree, 0, ptr::null_mut(), 0, &mut proof_len)
+            );
+            assert_eq!(1, proof_len);
+
+            assert_eq!(MtStatus::Ok, mt_root(tree, root.as_mut_ptr()));
+            let tree_root = (*tree).tree.root().unwrap();
+            assert_eq!(tree_root, CStr::from_ptr(root.as_ptr()).to_str().unwrap());
+            mt_free(tree);
+        }
+    }
+}
@@ -1,6 +1,156 @@
-use std::num::ParseIntError;
+use alloc::string::String;
+use core::error::Error;
+use core::fmt;
+#[cfg(feature = "std")]
+use std::io;
 
-pub enum UserInterfaceErrors {
-    NotEnoughArgumentsError(String),
-    NotCorrectTypeError(ParseIntError),
+#[derive(Debug)]
+pub enum BitcoinErrors {
+    InvalidTxidError(String),
+    InvalidHeaderError(usize),
+    MalformedPartialTreeError(String),
+}
+
+#[derive(Debug)]
+pub enum SigningErrors {
+    InvalidKeyError(String),
+    MalformedTreeHeadError(String),
+    EmptyTreeError,
+}
+
+#[derive(Debug)]
+pub enum MerkleTreeErrors {
+    AmbiguousDomainTagsError,
+    UnsupportedHashError(String),
+    InconsistentStoreError(String),
+}
+
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub enum WalErrors {
+    IoError(io::Error),
+    CorruptCheckpointError(String),
+    CorruptLogError(String),
+    RootMismatchError(String),
+    TreeError(MerkleTreeErrors),
+}
+
+#[cfg(feature = "std")]
+impl From<io::Error> for WalErrors {
+    fn from(e: io::Error) -> Self {
+        WalErrors::IoError(e)
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<MerkleTreeErrors> for WalErrors {
+    fn from(e: MerkleTreeErrors) -> Self {
+        WalErrors::TreeError(e)
+    }
+}
+
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub enum SyncErrors {
+    IoError(io::Error),
+    MalformedMessageError(String),
+    UnexpectedMessageError(String),
+}
+
+#[cfg(feature = "std")]
+impl From<io::Error> for SyncErrors {
+    fn from(e: io::Error) -> Self {
+        SyncErrors::IoError(e)
+    }
+}
+
+impl fmt::Display for BitcoinErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BitcoinErrors::InvalidTxidError(txid) => write!(f, "invalid txid: {txid}"),
+            BitcoinErrors::InvalidHeaderError(len) => {
+                write!(f, "a block header has 80 bytes, got {len}")
+            }
+            BitcoinErrors::MalformedPartialTreeError(reason) => {
+                write!(f, "malformed partial merkle tree: {reason}")
+            }
+        }
+    }
+}
+
+impl fmt::Display for SigningErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SigningErrors::InvalidKeyError(reason) => write!(f, "invalid key: {reason}"),
+            SigningErrors::MalformedTreeHeadError(reason) => {
+                write!(f, "malformed signed tree head: {reason}")
+            }
+            SigningErrors::EmptyTreeError => write!(f, "an empty tree has no root to sign"),
+        }
+    }
+}
+
+impl fmt::Display for MerkleTreeErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MerkleTreeErrors::AmbiguousDomainTagsError => {
+                write!(f, "a domain tag can't be a prefix of the other")
+            }
+            MerkleTreeErrors::UnsupportedHashError(hash) => {
+                write!(f, "the store of the tree can't hold {hash:?}")
+            }
+            MerkleTreeErrors::InconsistentStoreError(reason) => {
+                write!(f, "inconsistent store: {reason}")
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl fmt::Display for WalErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WalErrors::IoError(e) => write!(f, "{e}"),
+            WalErrors::CorruptCheckpointError(reason) => write!(f, "corrupt checkpoint: {reason}"),
+            WalErrors::CorruptLogError(reason) => write!(f, "corrupt log: {reason}"),
+            WalErrors::RootMismatchError(reason) => write!(f, "root mismatch: {reason}"),
+            WalErrors::TreeError(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl fmt::Display for SyncErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SyncErrors::IoError(e) => write!(f, "{e}"),
+            SyncErrors::MalformedMessageError(reason) => write!(f, "malformed message: {reason}"),
+            SyncErrors::UnexpectedMessageError(reason) => write!(f, "unexpected message: {reason}"),
+        }
+    }
+}
+
+impl Error for BitcoinErrors {}
+impl Error for SigningErrors {}
+impl Error for MerkleTreeErrors {}
+
+#[cfg(feature = "std")]
+impl Error for WalErrors {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            WalErrors::IoError(e) => Some(e),
+            WalErrors::TreeError(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Error for SyncErrors {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SyncErrors::IoError(e) => Some(e),
+            _ => None,
+        }
+    }
 }
@@ -0,0 +1,260 @@
+use std::collections::HashSet;
+use std::fmt::Write;
+
+use crate::merkle_tree::{MerkleTree, Padding};
+
+/// Renders the whole tree as a Graphviz digraph, with the full hashes. Leaves are labeled with their index and the
+/// padding nodes are dashed. If a leaf index is given, its path to the root and the nodes of its proof are highlighted
+pub fn to_dot(tree: &MerkleTree, proof_index: Option<usize>) -> String {
+    let (path, proof) = match proof_index {
+        Some(index) => highlighted_nodes(tree, index),
+        None => (HashSet::new(), HashSet::new()),
+    };
+
+    let mut dot = String::new();
+    dot.push_str("digraph merkle_tree {\n");
+    dot.push_str("    node [shape=box, fontname=\"monospace\"];\n");
+
+    for (level, nodes) in tree.levels().enumerate() {
+        let level = level as u32;
+        for (index, hash) in nodes.iter().enumerate() {
+            if tree.node(level, index).is_none() {
+                // Missing nodes of the promote padding
+                continue;
+            }
+
+            let mut label = escape(hash);
+            let mut styles = Vec::new();
+            let mut attributes = Vec::new();
+            if level == tree.depth() {
+                if index < tree.len() {
+                    label.push_str(&format!("\\nleaf {index}"));
+                } else {
+                    label.push_str("\\npadding");
+                }
+            }
+            if tree.is_padding_node(level, index) {
+                styles.push("dashed");
+            }
+            if path.contains(&(level, index)) {
+                attributes.push("color=red, penwidth=2".to_string());
+            }
+            if proof.contains(&(level, index)) {
+                styles.push("filled");
+                attributes.push("fillcolor=lightblue".to_string());
+            }
+            if !styles.is_empty() {
+                attributes.insert(0, format!("style=\"{}\"", styles.join(",")));
+            }
+
+            let _ = write!(dot, "    n{} [label=\"{label}\"", node_id(level, index));
+            for attribute in attributes {
+                let _ = write!(dot, ", {attribute}");
+            }
+            dot.push_str("];\n");
+        }
+    }
+
+    for level in 0..tree.depth() {
+        for index in 0..(1 << level) {
+            for child in [2 * index, 2 * index + 1] {
+                if tree.node(level, index).is_some() && tree.node(level + 1, child).is_some() {
+                    let _ = writeln!(
+                        dot,
+                        "    n{} -> n{};",
+                        node_id(level, index),
+                        node_id(level + 1, child)
+                    );
+                }
+            }
+        }
+    }
+
+    dot.push_str("}\n");
+    dot
+}
+
+/// Renders the tree as nested JSON objects, each with its hash and its children. Leaves have their index, and the
+/// padding leaves are marked as such
+pub fn to_json(tree: &MerkleTree) -> String {
+    let padding = match tree.padding() {
+        Padding::DuplicateLast => "duplicate-last",
+        Padding::Zero => "zero",
+        Padding::Promote => "promote",
+    };
+
+    let mut json = format!(
+        "{{\"leaves\":{},\"depth\":{},\"padding\":\"{padding}\",\"root\":",
+        tree.len(),
+        tree.depth()
+    );
+    if tree.is_empty() {
+        json.push_str("null");
+    } else {
+        write_json_node(tree, 0, 0, &mut json);
+    }
+    json.push('}');
+
+    json
+}
+
+fn write_json_node(tree: &MerkleTree, level: u32, index: usize, json: &mut String) {
+    let Some(hash) = tree.node(level, index) else {
+        json.push_str("null");
+        return;
+    };
+
+    let _ = write!(json, "{{\"hash\":\"{}\"", escape(&hash));
+    if level == tree.depth() {
+        if index < tree.len() {
+            let _ = write!(json, ",\"index\":{index}");
+        } else {
+            json.push_str(",\"padding\":true");
+        }
+    } else {
+        json.push_str(",\"left\":");
+        write_json_node(tree, level + 1, 2 * index, json);
+        json.push_str(",\"right\":");
+        write_json_node(tree, level + 1, 2 * index + 1, json);
+    }
+    json.push('}');
+}
+
+/// Escapes the quotes, backslashes and control characters of a node, as the leaves added with add can be any text.
+/// The result is valid both in a JSON string and in a quoted DOT label
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(escaped, "\\u{:04x}", c as u32);
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Same numbering as the positions in the vector of the tree
+fn node_id(level: u32, index: usize) -> usize {
+    (1 << level) - 1 + index
+}
+
+/// The (level, index) of the nodes from the leaf to the root, and the ones of its proof
+type Positions = HashSet<(u32, usize)>;
+
+fn highlighted_nodes(tree: &MerkleTree, index: usize) -> (Positions, Positions) {
+    if index >= tree.len() {
+        return (HashSet::new(), HashSet::new());
+    }
+
+    let path = (0..=tree.depth())
+        .map(|height| (tree.depth() - height, index >> height))
+        .collect();
+    let proof = tree.proof_positions(index).into_iter().collect();
+
+    (path, proof)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{to_dot, to_json};
+    use crate::merkle_tree::{MerkleTree, Padding};
+
+    #[test]
+    fn test_01_dot_has_every_node_with_its_full_hash() {
+        let tree = MerkleTree::build(vec!["a", "b", "c"], true);
+        let dot = to_dot(&tree, None);
+
+        assert!(dot.starts_with("digraph merkle_tree {"));
+        for level in tree.levels() {
+            for hash in level {
+                assert!(dot.contains(hash.as_str()));
+            }
+        }
+        assert_eq!(6, dot.matches("->").count());
+        assert!(dot.contains("\\nleaf 2\"];"));
+    }
+
+    #[test]
+    fn test_02_dot_marks_the_padding_nodes() {
+        let tree = MerkleTree::build(vec!["a", "b", "c"], true);
+        let dot = to_dot(&tree, None);
+
+        assert!(dot.contains("n6 [label=\"2e7d2c03a9507ae265ecf5b5356885a53393a2029d241394997265a1a25aefc6\\npadding\", style=\"dashed\"];"));
+        assert_eq!(1, dot.matches("dashed").count());
+    }
+
+    #[test]
+    fn test_03_dot_highlights_the_proof_path() {
+        let tree = MerkleTree::build(vec!["a", "b", "c", "d"], true);
+        let dot = to_dot(&tree, Some(1));
+
+        // The leaf, its parent and the root are on the path, and the proof is leaf 0 and the right node of level 1
+        assert_eq!(3, dot.matches("color=red").count());
+        assert_eq!(2, dot.matches("fillcolor=lightblue").count());
+        assert!(dot.contains("n4 [label=\"3e23e8160039594a33894f6564e1b1348bbd7a0088d42c4acb73eeaed59c009d\\nleaf 1\", color=red, penwidth=2];"));
+        assert!(dot.contains("n3 [label=\"ca978112ca1bbdcafac231b39a23dc4da786eff8147c4e72b9807785afee48bb\\nleaf 0\", style=\"filled\", fillcolor=lightblue];"));
+    }
+
+    #[test]
+    fn test_04_dot_skips_the_missing_nodes_of_promote_padding() {
+        let tree = MerkleTree::build(vec!["a", "b", "c"], true).with_padding(Padding::Promote);
+        let dot = to_dot(&tree, None);
+
+        assert!(!dot.contains("n6 "));
+        assert_eq!(5, dot.matches("->").count());
+    }
+
+    #[test]
+    fn test_05_json_is_nested_from_the_root() {
+        let tree = MerkleTree::build(vec!["a"], true);
+
+        assert_eq!(
+            format!(
+                "{{\"leaves\":1,\"depth\":1,\"padding\":\"duplicate-last\",\"root\":{{\"hash\":\"{}\",\"left\":{{\"hash\":\"{a}\",\"index\":0}},\"right\":{{\"hash\":\"{a}\",\"padding\":true}}}}}}",
+                tree.root().unwrap(),
+                a = "ca978112ca1bbdcafac231b39a23dc4da786eff8147c4e72b9807785afee48bb"
+            ),
+            to_json(&tree)
+        );
+    }
+
+    #[test]
+    fn test_06_json_of_empty_and_promoted_trees() {
+        assert_eq!(
+            "{\"leaves\":0,\"depth\":0,\"padding\":\"duplicate-last\",\"root\":null}",
+            to_json(&MerkleTree::new())
+        );
+
+        let tree = MerkleTree::build(vec!["a"], true).with_padding(Padding::Promote);
+        assert!(to_json(&tree).ends_with(",\"index\":0},\"right\":null}}"));
+    }
+
+    #[test]
+    fn test_07_dot_escapes_the_labels() {
+        let mut tree = MerkleTree::new();
+        tree.add("x\"y\\z".to_string());
+        tree.add("a\nb\u{1}".to_string());
+        let dot = to_dot(&tree, None);
+
+        assert!(dot.contains("[label=\"x\\\"y\\\\z\\nleaf 0\"];"));
+        assert!(dot.contains("[label=\"a\\nb\\u0001\\nleaf 1\"];"));
+    }
+
+    #[test]
+    fn test_08_json_escapes_the_hashes() {
+        let mut tree = MerkleTree::new();
+        tree.add("x\"y\\z".to_string());
+        tree.add("a\nb\u{1}".to_string());
+
+        let json: serde_json::Value = serde_json::from_str(&to_json(&tree)).unwrap();
+        assert_eq!("x\"y\\z", json["root"]["left"]["hash"]);
+        assert_eq!("a\nb\u{1}", json["root"]["right"]["hash"]);
+    }
+}
@@ -0,0 +1,378 @@
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use sha2::{Digest, Sha256};
+
+use crate::errors::MerkleTreeErrors;
+
+/// Tags hashed before the data of a leaf and before the children of a node. Without them, the two hashes of an
+/// inner node concatenated could be presented as the data of a leaf (second preimage attack). It also says how the
+/// leaves and nodes are hashed: the hash function and how the children are fed to it
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct DomainSeparation {
+    leaf_tag: Vec<u8>,
+    node_tag: Vec<u8>,
+    node_encoding: NodeEncoding,
+    hash_function: HashFunction,
+}
+
+/// How the children of a node are fed to its hash
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NodeEncoding {
+    /// Their lowercase hex, as the tree always hashed them
+    #[default]
+    Hex,
+    /// Their 32 bytes, as Certificate Transparency and most other implementations do. With the rfc6962 tags and the
+    /// promote padding the tree is the one of RFC 6962
+    Raw,
+}
+
+/// The function the leaves and the nodes are hashed with. All of them give 32 byte hashes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HashFunction {
+    #[default]
+    Sha256,
+    /// Much faster than SHA256 with big leaves, as BLAKE3 is a tree itself: the data is split in 1 KiB chunks that
+    /// are hashed in parallel with SIMD
+    #[cfg(feature = "blake3")]
+    Blake3,
+    /// BLAKE3 keyed with one key for the leaves and another for the nodes, which separates them without tags. See
+    /// DomainSeparation::blake3_keyed
+    #[cfg(feature = "blake3")]
+    Blake3Keyed {
+        leaf_key: [u8; 32],
+        node_key: [u8; 32],
+    },
+}
+
+impl DomainSeparation {
+    /// Tags can be raw bytes or personalization strings. If one of them is a prefix of the other (or both are
+    /// equal) the domains could overlap, so they are rejected
+    pub fn new(
+        leaf_tag: impl Into<Vec<u8>>,
+        node_tag: impl Into<Vec<u8>>,
+    ) -> Result<Self, MerkleTreeErrors> {
+        let leaf_tag = leaf_tag.into();
+        let node_tag = node_tag.into();
+
+        if leaf_tag.starts_with(&node_tag) || node_tag.starts_with(&leaf_tag) {
+            return Err(MerkleTreeErrors::AmbiguousDomainTagsError);
+        }
+
+        Ok(DomainSeparation {
+            leaf_tag,
+            node_tag,
+            ..DomainSeparation::default()
+        })
+    }
+
+    /// The 0x00 and 0x01 prefixes used by Certificate Transparency
+    pub fn rfc6962() -> Self {
+        DomainSeparation {
+            leaf_tag: vec![0x00],
+            node_tag: vec![0x01],
+            ..DomainSeparation::default()
+        }
+    }
+
+    #[cfg(feature = "blake3")]
+    /// No tags, the leaves and the nodes are hashed with BLAKE3 keyed with different keys. Equal keys are rejected
+    pub fn blake3_keyed(leaf_key: [u8; 32], node_key: [u8; 32]) -> Result<Self, MerkleTreeErrors> {
+        if leaf_key == node_key {
+            return Err(MerkleTreeErrors::AmbiguousDomainTagsError);
+        }
+
+        Ok(DomainSeparation::default()
+            .with_hash_function(HashFunction::Blake3Keyed { leaf_key, node_key }))
+    }
+
+    pub fn with_node_encoding(mut self, node_encoding: NodeEncoding) -> Self {
+        self.node_encoding = node_encoding;
+        self
+    }
+
+    pub fn with_hash_function(mut self, hash_function: HashFunction) -> Self {
+        self.hash_function = hash_function;
+        self
+    }
+
+    /// Without tags nor keys, the hashes are the plain hashes of the data
+    pub fn is_disabled(&self) -> bool {
+        #[cfg(feature = "blake3")]
+        if let HashFunction::Blake3Keyed { .. } = self.hash_function {
+            return false;
+        }
+        self.leaf_tag.is_empty() && self.node_tag.is_empty()
+    }
+
+    pub fn leaf_tag(&self) -> &[u8] {
+        &self.leaf_tag
+    }
+
+    pub fn node_tag(&self) -> &[u8] {
+        &self.node_tag
+    }
+
+    pub fn node_encoding(&self) -> NodeEncoding {
+        self.node_encoding
+    }
+
+    pub fn hash_function(&self) -> HashFunction {
+        self.hash_function
+    }
+}
+
+/// Whether a leaf or a node is being hashed, as they get different tags and keys
+#[derive(Clone, Copy)]
+enum Kind {
+    Leaf,
+    Node,
+}
+
+/// The state of the hash function of a domain, with the tag already fed
+// The BLAKE3 state is big, but it only lives on the stack while hashing and boxing it would allocate
+#[allow(clippy::large_enum_variant)]
+enum Hasher {
+    Sha256(Sha256),
+    #[cfg(feature = "blake3")]
+    Blake3(blake3::Hasher),
+}
+
+impl Hasher {
+    fn new(domain: &DomainSeparation, kind: Kind) -> Self {
+        let mut hasher = match domain.hash_function {
+            HashFunction::Sha256 => Hasher::Sha256(Sha256::new()),
+            #[cfg(feature = "blake3")]
+            HashFunction::Blake3 => Hasher::Blake3(blake3::Hasher::new()),
+            #[cfg(feature = "blake3")]
+            HashFunction::Blake3Keyed { leaf_key, node_key } => {
+                let key = match kind {
+                    Kind::Leaf => leaf_key,
+                    Kind::Node => node_key,
+                };
+                Hasher::Blake3(blake3::Hasher::new_keyed(&key))
+            }
+        };
+        match kind {
+            Kind::Leaf => hasher.update(&domain.leaf_tag),
+            Kind::Node => hasher.update(&domain.node_tag),
+        }
+        hasher
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Sha256(hasher) => hasher.update(data),
+            #[cfg(feature = "blake3")]
+            Hasher::Blake3(hasher) => {
+                hasher.update(data);
+            }
+        }
+    }
+
+    fn finalize(self) -> [u8; 32] {
+        match self {
+            Hasher::Sha256(hasher) => hasher.finalize().into(),
+            #[cfg(feature = "blake3")]
+            Hasher::Blake3(hasher) => hasher.finalize().into(),
+        }
+    }
+}
+
+/// Hash of a leaf from its data, hex encoded
+pub fn hash_leaf(domain: &DomainSeparation, data: &[u8]) -> String {
+    let mut hasher = Hasher::new(domain, Kind::Leaf);
+    hasher.update(data);
+    hex::encode(hasher.finalize())
+}
+
+/// Hash of an inner node, with the children fed as the node encoding says. With the raw encoding a child that is not
+/// 64 hex characters (anything can be added to the tree) is fed as it is
+pub fn hash_node(domain: &DomainSeparation, hash_left: &str, hash_right: &str) -> String {
+    let mut hasher = Hasher::new(domain, Kind::Node);
+    for hash in [hash_left, hash_right] {
+        let mut bytes = [0; 32];
+        match domain.node_encoding {
+            NodeEncoding::Raw if hex::decode_to_slice(hash, &mut bytes).is_ok() => {
+                hasher.update(&bytes)
+            }
+            _ => hasher.update(hash.as_bytes()),
+        }
+    }
+    hex::encode(hasher.finalize())
+}
+
+/// Same as hash_node, with the hashes as bytes and without allocating. With the hex encoding they are encoded in
+/// lowercase, like the tree encodes the hashes it computes
+pub fn hash_node_bytes(
+    domain: &DomainSeparation,
+    hash_left: &[u8; 32],
+    hash_right: &[u8; 32],
+) -> [u8; 32] {
+    let mut hasher = Hasher::new(domain, Kind::Node);
+    let mut encoded = [0; 64];
+    for hash in [hash_left, hash_right] {
+        match domain.node_encoding {
+            NodeEncoding::Hex => {
+                hex::encode_to_slice(hash, &mut encoded).expect("64 bytes fit 32 hex encoded ones");
+                hasher.update(&encoded);
+            }
+            NodeEncoding::Raw => hasher.update(hash),
+        }
+    }
+    hasher.finalize()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{hash_leaf, hash_node, hash_node_bytes, DomainSeparation, NodeEncoding};
+
+    #[test]
+    fn test_01_disabled_domain_separation_is_plain_sha256() {
+        let domain = DomainSeparation::default();
+
+        assert!(domain.is_disabled());
+        assert_eq!(
+            "ca978112ca1bbdcafac231b39a23dc4da786eff8147c4e72b9807785afee48bb",
+            hash_leaf(&domain, b"a")
+        );
+    }
+
+    #[test]
+    fn test_02_leaf_and_node_hashes_of_the_same_bytes_differ() {
+        // Without tags, a leaf whose data is the concatenation of two hashes is hashed like their parent
+        let left = hash_leaf(&DomainSeparation::default(), b"a");
+        let right = hash_leaf(&DomainSeparation::default(), b"b");
+        let concatenated = format!("{left}{right}");
+
+        let plain = DomainSeparation::default();
+        assert_eq!(
+            hash_node(&plain, &left, &right),
+            hash_leaf(&plain, concatenated.as_bytes())
+        );
+
+        let separated = DomainSeparation::rfc6962();
+        assert_ne!(
+            hash_node(&separated, &left, &right),
+            hash_leaf(&separated, concatenated.as_bytes())
+        );
+    }
+
+    #[test]
+    fn test_03_personalization_strings_are_accepted_as_tags() {
+        let domain = DomainSeparation::new("my-app/leaf", "my-app/node").unwrap();
+
+        assert_eq!(b"my-app/leaf", domain.leaf_tag());
+        assert_ne!(
+            hash_leaf(&domain, b"a"),
+            hash_leaf(&DomainSeparation::rfc6962(), b"a")
+        );
+    }
+
+    #[test]
+    fn test_04_overlapping_tags_are_rejected() {
+        assert!(DomainSeparation::new("tag", "tag").is_err());
+        assert!(DomainSeparation::new("leaf", "leaf-node").is_err());
+        assert!(DomainSeparation::new(vec![], vec![1]).is_err());
+        assert!(DomainSeparation::new(vec![0], vec![1]).is_ok());
+    }
+
+    #[test]
+    fn test_05_hashing_nodes_as_bytes_matches_the_hex_hashing() {
+        for encoding in [NodeEncoding::Hex, NodeEncoding::Raw] {
+            let domain = DomainSeparation::rfc6962().with_node_encoding(encoding);
+            let left = hash_leaf(&domain, b"a");
+            let right = hash_leaf(&domain, b"b");
+
+            let mut left_bytes = [0; 32];
+            let mut right_bytes = [0; 32];
+            hex::decode_to_slice(&left, &mut left_bytes).unwrap();
+            hex::decode_to_slice(&right, &mut right_bytes).unwrap();
+            assert_eq!(
+                hash_node(&domain, &left, &right),
+                hex::encode(hash_node_bytes(&domain, &left_bytes, &right_bytes))
+            );
+        }
+    }
+
+    #[test]
+    fn test_06_raw_nodes_hash_the_bytes_of_the_children() {
+        let domain = DomainSeparation::rfc6962().with_node_encoding(NodeEncoding::Raw);
+        let left = hash_leaf(&domain, b"");
+        let right = hash_leaf(&domain, b"\x00");
+
+        // The root of the first two leaves of the certificate-transparency reference tests
+        assert_eq!(
+            "fac54203e7cc696cf0dfcb42c92a1d9dbaf70ad9e621f4bd8d98662f00e3c125",
+            hash_node(&domain, &left, &right)
+        );
+        assert_ne!(
+            hash_node(&DomainSeparation::rfc6962(), &left, &right),
+            hash_node(&domain, &left, &right)
+        );
+        // Strings that are not hashes are still hashed, as they are
+        assert_ne!(hash_node(&domain, "a", "b"), hash_node(&domain, "b", "a"));
+    }
+
+    #[cfg(feature = "blake3")]
+    #[test]
+    fn test_07_blake3_hashes_leaves_and_nodes() {
+        use super::HashFunction;
+
+        let domain = DomainSeparation::default().with_hash_function(HashFunction::Blake3);
+        assert!(domain.is_disabled());
+        // BLAKE3 of the empty input, from the test vectors of the reference implementation
+        assert_eq!(
+            "af1349b9f5f9a1a6a0404dea36dcc9499bcb25c9adc112b7cc9a93cae41f3262",
+            hash_leaf(&domain, b"")
+        );
+
+        let left = hash_leaf(&domain, b"a");
+        let right = hash_leaf(&domain, b"b");
+        assert_ne!(hash_leaf(&DomainSeparation::default(), b"a"), left);
+        assert_eq!(
+            blake3::hash(format!("{left}{right}").as_bytes())
+                .to_hex()
+                .as_str(),
+            hash_node(&domain, &left, &right)
+        );
+    }
+
+    #[cfg(feature = "blake3")]
+    #[test]
+    fn test_08_blake3_keyed_mode_separates_leaves_and_nodes() {
+        let leaf_key = [1; 32];
+        let node_key = [2; 32];
+        let domain = DomainSeparation::blake3_keyed(leaf_key, node_key)
+            .unwrap()
+            .with_node_encoding(NodeEncoding::Raw);
+        assert!(!domain.is_disabled());
+        assert!(DomainSeparation::blake3_keyed(leaf_key, leaf_key).is_err());
+
+        let left = hash_leaf(&domain, b"a");
+        let right = hash_leaf(&domain, b"b");
+        assert_eq!(blake3::keyed_hash(&leaf_key, b"a").to_hex().as_str(), left);
+
+        // A leaf with the bytes of both children is not hashed like their parent
+        let mut children = hex::decode(&left).unwrap();
+        children.extend(hex::decode(&right).unwrap());
+        assert_eq!(
+            blake3::keyed_hash(&node_key, &children).to_hex().as_str(),
+            hash_node(&domain, &left, &right)
+        );
+        assert_ne!(
+            hash_leaf(&domain, &children),
+            hash_node(&domain, &left, &right)
+        );
+
+        let mut left_bytes = [0; 32];
+        let mut right_bytes = [0; 32];
+        hex::decode_to_slice(&left, &mut left_bytes).unwrap();
+        hex::decode_to_slice(&right, &mut right_bytes).unwrap();
+        assert_eq!(
+            hash_node(&domain, &left, &right),
+            hex::encode(hash_node_bytes(&domain, &left_bytes, &right_bytes))
+        );
+    }
+}
@@ -0,0 +1,58 @@
+//! A Merkle Tree of SHA-256 hashes (or BLAKE3 ones, with the `blake3` feature), with inclusion proofs that can be
+//! checked without the tree.
+//!
+//! ```
+//! use rusty_merkle_tree::{MerkleTree, Padding, DomainSeparation};
+//!
+//! let tree = MerkleTree::build(vec!["a", "b", "c"], true);
+//! let root = tree.root().unwrap();
+//!
+//! let proof = tree.prove(1).unwrap();
+//! assert!(proof.verify(&root, Padding::DuplicateLast, &DomainSeparation::default()));
+//! ```
+//!
+//! The types re-exported here are the stable API. The modules give access to the rest: bitcoin merkle blocks,
+//! snapshots, file backed stores, the write-ahead log, signed tree heads and replica sync
+//!
+//! Without the default `std` feature the crate is `no_std` and only needs `alloc`. The tree, the proofs and the
+//! snapshots work the same, and [`proof::FixedProof`] verifies proofs without allocating at all
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+pub mod bitcoin;
+#[cfg(feature = "capi")]
+pub mod capi;
+pub mod errors;
+#[cfg(feature = "std")]
+pub mod export;
+pub mod hashing;
+pub mod merkle_tree;
+#[cfg(feature = "std")]
+pub mod printer;
+pub mod proof;
+#[cfg(feature = "python")]
+pub mod python;
+#[cfg(feature = "std")]
+pub mod shared;
+#[cfg(feature = "std")]
+pub mod signed_tree_head;
+pub mod snapshot;
+pub mod store;
+#[cfg(feature = "std")]
+pub mod sync;
+#[cfg(feature = "std")]
+pub mod wal;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+pub use errors::{BitcoinErrors, MerkleTreeErrors, SigningErrors};
+#[cfg(feature = "std")]
+pub use errors::{SyncErrors, WalErrors};
+pub use hashing::{DomainSeparation, HashFunction, NodeEncoding};
+pub use merkle_tree::{MerkleTree, Padding};
+pub use proof::{ConsistencyProof, FixedProof, MerkleProof};
+#[cfg(feature = "std")]
+pub use shared::SharedMerkleTree;
+pub use snapshot::MerkleSnapshot;
@@ -1,11 +1,54 @@
-use sha2::{Digest, Sha256};
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+// Without std there is no hasher to build a HashMap, so the leaf index is kept sorted
+#[cfg(not(feature = "std"))]
+use alloc::collections::BTreeMap as HashMap;
+#[cfg(feature = "std")]
+use std::collections::HashMap;
+#[cfg(feature = "std")]
+use std::io;
+
+use crate::errors::MerkleTreeErrors;
+use crate::hashing::{self, DomainSeparation};
+#[cfg(feature = "std")]
+use crate::printer::{self, PrintOptions};
+use crate::proof::{root_from_proof, sibling_is_missing, ConsistencyProof, MerkleProof};
+use crate::snapshot::{self, MerkleSnapshot, WeakSnapshot};
+use crate::store::{MemoryStore, NodeStore};
+
+/// Hash used to fill the empty leaves when padding with zeros
+const ZERO_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// How the leaves that are left until the next power of two are filled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Padding {
+    /// Copies of the last inserted hash. Note that with it [a, b, c] and [a, b, c, c] share root
+    #[default]
+    DuplicateLast,
+    /// A fixed hash of zeros
+    Zero,
+    /// Nothing is filled, and a node without right sibling is promoted unchanged to the upper level
+    Promote,
+}
 
-/// This structure represents a Merkle Tree, with a Vector
+/// This structure represents a Merkle Tree, with its nodes in level order in a NodeStore
 pub struct MerkleTree {
-    /// I've chosen a vector temporarily bc it was the simpler way to do it
-    elements: Vec<String>,
+    /// A vector by default, it can be changed to a file for trees that don't fit in memory
+    elements: Box<dyn NodeStore>,
     /// Ammount of inserted leaf nodes (without reapeated ones)
     inserted_elements_amount: usize,
+    padding: Padding,
+    domain: DomainSeparation,
+    /// Optional map from each leaf hash to the positions where it was inserted, to find them without a linear search
+    leaf_index: Option<HashMap<String, Vec<usize>>>,
+    /// The last snapshot taken, to share with the next one the subtrees that didn't change. It's weak so the tree
+    /// doesn't keep a copy of all its nodes alive once nobody uses the snapshot
+    last_snapshot: Option<WeakSnapshot>,
+    /// Hash of a subtree with only padding leaves, for each height. Those subtrees are all equal, so their nodes are
+    /// not kept up to date in the store and are read from here
+    padding_hashes: Vec<String>,
 }
 
 impl Default for MerkleTree {
@@ -16,222 +59,698 @@ impl Default for MerkleTree {
 
 impl MerkleTree {
     pub fn new() -> Self {
-        let elements = Vec::new();
         MerkleTree {
-            elements,
+            elements: Box::new(MemoryStore::default()),
             inserted_elements_amount: 0,
+            padding: Padding::default(),
+            domain: DomainSeparation::default(),
+            leaf_index: None,
+            last_snapshot: None,
+            padding_hashes: Vec::new(),
         }
     }
 
     pub fn build(hashes: Vec<&str>, unhashed: bool) -> Self {
         let mut tree = MerkleTree::new();
 
-        for hash in hashes {
-            if unhashed {
-                tree.add_unhashed(hash.to_string());
-            } else {
-                tree.add(hash.to_string());
+        let leaves = hashes
+            .into_iter()
+            .map(|hash| {
+                if unhashed {
+                    tree.hash_leaf(hash)
+                } else {
+                    hash.to_string()
+                }
+            })
+            .collect();
+        tree.build_from_leaves(leaves);
+
+        tree
+    }
+
+    /// Removes all the leaves, keeping the padding and the domain separation
+    pub fn clear(&mut self) {
+        self.elements.clear();
+        self.inserted_elements_amount = 0;
+        self.elements.set_leaves_len(0);
+        self.last_snapshot = None;
+        self.padding_hashes.clear();
+        if let Some(leaf_index) = &mut self.leaf_index {
+            leaf_index.clear();
+        }
+    }
+
+    /// Keeps a map from hashes to leaf indexes, updated on every add, so the lookups don't go through all the leaves
+    pub fn with_leaf_index(mut self) -> Self {
+        let mut leaf_index: HashMap<String, Vec<usize>> = HashMap::new();
+        for (i, leaf) in self.leaves().into_iter().enumerate() {
+            leaf_index.entry(leaf).or_default().push(i);
+        }
+        self.leaf_index = Some(leaf_index);
+
+        self
+    }
+
+    /// Index of the first leaf with the hash. The padding copies are not taken into account
+    pub fn index_of(&self, hash: &str) -> Option<usize> {
+        self.indices_of(hash).first().copied()
+    }
+
+    /// Same as index_of, hashing the text as add_unhashed does
+    pub fn index_of_unhashed(&self, unhashed_text: &str) -> Option<usize> {
+        self.index_of(&self.hash_leaf(unhashed_text))
+    }
+
+    /// All the indexes of the leaves with the hash, in insertion order, as the same hash can be added many times
+    pub fn indices_of(&self, hash: &str) -> Vec<usize> {
+        match &self.leaf_index {
+            Some(leaf_index) => leaf_index.get(hash).cloned().unwrap_or_default(),
+            None => {
+                let first_leaf = self.first_leaf();
+                (0..self.inserted_elements_amount)
+                    .filter(|i| self.node_at(first_leaf + i) == hash)
+                    .collect()
             }
         }
+    }
 
-        tree
+    /// Same as indices_of, hashing the text as add_unhashed does
+    pub fn indices_of_unhashed(&self, unhashed_text: &str) -> Vec<usize> {
+        self.indices_of(&self.hash_leaf(unhashed_text))
+    }
+
+    pub fn contains(&self, hash: &str) -> bool {
+        self.index_of(hash).is_some()
     }
 
-    fn hash_text(unhashed_text: &str) -> String {
-        let mut hasher = Sha256::new();
-        hasher.update(unhashed_text);
-        let hashed: [u8; 32] = hasher.finalize().into();
-        hex::encode(hashed)
+    /// Changes the padding strategy. If the tree already has elements, it is rebuilt with the new strategy
+    pub fn with_padding(mut self, padding: Padding) -> Self {
+        self.padding = padding;
+        self.build_from_leaves(self.leaves());
+
+        self
+    }
+
+    pub fn padding(&self) -> Padding {
+        self.padding
+    }
+
+    /// Changes the tags used to hash leaves and nodes. The leaves already inserted keep their hashes, only the
+    /// nodes above them are recalculated
+    pub fn with_domain_separation(mut self, domain: DomainSeparation) -> Self {
+        self.domain = domain;
+        self.build_from_leaves(self.leaves());
+
+        self
+    }
+
+    pub fn domain_separation(&self) -> &DomainSeparation {
+        &self.domain
+    }
+
+    /// Changes where the nodes are kept, copying the current ones to the new store. Fails if the store can't hold
+    /// some of them, like the file store with leaves that are not hashes
+    pub fn with_store(mut self, store: impl NodeStore + 'static) -> Result<Self, MerkleTreeErrors> {
+        let mut store: Box<dyn NodeStore> = Box::new(store);
+        if let Some(hash) = self.leaves().into_iter().find(|leaf| !store.holds(leaf)) {
+            return Err(MerkleTreeErrors::UnsupportedHashError(hash));
+        }
+        store.clear();
+        store.resize(self.elements.len(), "");
+        for pos in 0..self.elements.len() {
+            store.set(pos, &self.elements.get(pos));
+        }
+        store.set_leaves_len(self.len());
+        self.elements = store;
+
+        Ok(self)
+    }
+
+    /// Takes a store that already has the nodes of a tree, like a FileStore opened again, instead of copying the
+    /// current nodes to it. The padding and domain separation must be the ones the tree was built with, as only the
+    /// nodes are saved. Fails if the store doesn't keep the amount of leaves or has another amount of nodes
+    pub fn with_existing_store(
+        mut self,
+        store: impl NodeStore + 'static,
+    ) -> Result<Self, MerkleTreeErrors> {
+        let inconsistent = |reason: String| Err(MerkleTreeErrors::InconsistentStoreError(reason));
+        let Some(len) = store.leaves_len() else {
+            return inconsistent("the store doesn't keep the amount of leaves".to_string());
+        };
+        let nodes = match len {
+            0 => Some(0),
+            _ => len
+                .max(2)
+                .checked_next_power_of_two()
+                .and_then(|capacity| capacity.checked_mul(2))
+                .map(|double| double - 1),
+        };
+        if nodes != Some(store.len()) {
+            return inconsistent(format!(
+                "a tree of {len} leaves can't have {} nodes",
+                store.len()
+            ));
+        }
+
+        self.elements = Box::new(store);
+        self.inserted_elements_amount = len;
+        self.last_snapshot = None;
+        self.padding_hashes.clear();
+        if len > 0 {
+            self.update_padding_hashes();
+        }
+        if self.leaf_index.is_some() {
+            self = self.with_leaf_index();
+        }
+        Ok(self)
     }
 
-    fn combine_hashes(hash_left: &str, hash_right: &str) -> String {
-        let mut hasher = Sha256::new();
-        hasher.update(hash_left);
-        hasher.update(hash_right);
-        let hashed: [u8; 32] = hasher.finalize().into();
-        hex::encode(hashed)
+    /// If the store of the tree can keep the hash as a leaf. Any string can be kept in memory, while the file store
+    /// only keeps 32 byte hashes
+    pub fn can_hold(&self, hash: &str) -> bool {
+        self.elements.holds(hash)
     }
 
+    /// Replaces the leaves with already hashed ones, hashing each level a single time. Unlike build it keeps the
+    /// store, so big trees can be built straight into a file. Panics if the store can't hold a leaf, see can_hold
+    pub fn with_leaves(mut self, hashes: Vec<String>) -> Self {
+        self.build_from_leaves(hashes);
+        self
+    }
+
+    #[cfg(feature = "std")]
+    /// Saves the nodes written so far, if the store keeps them in a file
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.elements.flush()
+    }
+
+    fn hash_leaf(&self, unhashed_text: &str) -> String {
+        hashing::hash_leaf(&self.domain, unhashed_text.as_bytes())
+    }
+
+    fn hash_node(&self, hash_left: &str, hash_right: &str) -> String {
+        hashing::hash_node(&self.domain, hash_left, hash_right)
+    }
+
+    /// Leaves added with add are expected to be already hashed, so only the ones added this way get the leaf tag
     pub fn add_unhashed(&mut self, unhashed_text: String) {
-        let hashed_string = MerkleTree::hash_text(&unhashed_text);
+        let hashed_string = self.hash_leaf(&unhashed_text);
 
         self.add(hashed_string);
     }
 
-    /// The logic is: first expand the tree if needed, second insert the element, and then recalculate the middle and root hashes
-    pub fn add(&mut self, hashed_string: String) {
-        self.expand_tree();
+    /// Same as add, but returns an error instead of panicking if the store can't hold the hash
+    pub fn try_add(&mut self, hashed_string: String) -> Result<(), MerkleTreeErrors> {
+        if !self.can_hold(&hashed_string) {
+            return Err(MerkleTreeErrors::UnsupportedHashError(hashed_string));
+        }
+        self.add(hashed_string);
+        Ok(())
+    }
 
+    /// The logic is: first expand the tree if needed, second insert the element, and then recalculate the middle and root hashes.
+    /// Panics if the store can't hold the hash, see can_hold and try_add
+    pub fn add(&mut self, hashed_string: String) {
+        let expanded = self.expand_tree();
+
+        let position = self.inserted_elements_amount;
+        if let Some(leaf_index) = &mut self.leaf_index {
+            leaf_index
+                .entry(hashed_string.clone())
+                .or_default()
+                .push(position);
+        }
         self.insert_hash(hashed_string);
+        if expanded || self.padding == Padding::DuplicateLast {
+            self.update_padding_hashes();
+        }
 
-        self.rehash_tree(0);
+        // The nodes after the path of the leaf are all padding, so only the path has to be hashed again
+        self.rehash_tree(position, position);
     }
 
-    /// When depth increase is needed, then insert the middle hash nodes required to calculate all the leaf hashes of the level
-    fn expand_tree(&mut self) {
-        if self.inserted_elements_amount == 0 {
-            self.elements.insert(0, "".to_string());
+    /// Same as extend, but adds none of the leaves and returns an error if the store can't hold one of them
+    pub fn try_extend(&mut self, hashes: Vec<String>) -> Result<(), MerkleTreeErrors> {
+        if let Some(hash) = hashes.iter().find(|hash| !self.can_hold(hash)) {
+            return Err(MerkleTreeErrors::UnsupportedHashError(hash.clone()));
         }
-        // Needed this bc 1 is power of two and should not execute the logic that is inside the lower if
-        if self.inserted_elements_amount == 1 {
+        self.extend(hashes);
+        Ok(())
+    }
+
+    /// Adds many already hashed leaves, hashing the nodes above them a single time instead of once per leaf. Panics
+    /// if the store can't hold one of them, see can_hold and try_extend
+    pub fn extend(&mut self, hashes: impl IntoIterator<Item = String>) {
+        let first = self.inserted_elements_amount;
+        for hashed_string in hashes {
+            // The nodes moved by an expansion are hashed below if they are above the new leaves
+            self.expand_tree();
+            if let Some(leaf_index) = &mut self.leaf_index {
+                leaf_index
+                    .entry(hashed_string.clone())
+                    .or_default()
+                    .push(self.inserted_elements_amount);
+            }
+            self.insert_hash(hashed_string);
+        }
+        if self.inserted_elements_amount == first {
             return;
         }
-        if MerkleTree::number_is_power_of_two(self.inserted_elements_amount as f32) {
-            // The need of the for is to insert the non_leaf_nodes that will be used to calculate the root hash
-            for i in 0..self.inserted_elements_amount {
-                // Note: self.inserted_elements_amount is a proxy of the number of copies
-                self.elements
-                    .insert(self.inserted_elements_amount - 1 + i, "".to_string());
-                // These are empty strings because they will be calculated in the rehash_tree function from lower nodes
+
+        self.update_padding_hashes();
+        self.rehash_tree(first, self.inserted_elements_amount - 1);
+    }
+
+    /// Places all the leaves at once and hashes each level a single time, instead of adding them one by one
+    pub(crate) fn build_from_leaves(&mut self, leaves: Vec<String>) {
+        self.inserted_elements_amount = leaves.len();
+        self.elements.set_leaves_len(leaves.len());
+        self.last_snapshot = None;
+        if let Some(leaf_index) = &mut self.leaf_index {
+            leaf_index.clear();
+            for (i, leaf) in leaves.iter().enumerate() {
+                leaf_index.entry(leaf.clone()).or_default().push(i);
             }
         }
+        if leaves.is_empty() {
+            self.elements.clear();
+            self.padding_hashes.clear();
+            return;
+        }
+
+        let capacity = leaves.len().next_power_of_two().max(2);
+        self.elements.clear();
+        self.elements.resize(2 * capacity - 1, &self.padding_hash());
+        for (i, leaf) in leaves.iter().enumerate() {
+            self.elements.set(capacity - 1 + i, leaf);
+        }
+
+        self.update_padding_hashes();
+        self.rehash_tree(0, leaves.len() - 1);
     }
 
-    fn number_is_power_of_two(num: f32) -> bool {
-        if num <= 0.0 {
-            return false;
+    /// Amount of leaves the tree has room for, counting the padding ones
+    fn capacity(&self) -> usize {
+        self.elements.len().div_ceil(2)
+    }
+
+    /// Amount of levels below the root, 0 for an empty tree
+    pub fn depth(&self) -> u32 {
+        self.capacity().max(1).trailing_zeros()
+    }
+
+    /// Position in the vector of the first leaf
+    fn first_leaf(&self) -> usize {
+        self.capacity().saturating_sub(1)
+    }
+
+    /// The inserted leaves in order, without the padding
+    pub fn leaves(&self) -> Vec<String> {
+        let first_leaf = self.first_leaf();
+        (0..self.inserted_elements_amount)
+            .map(|i| self.node_at(first_leaf + i))
+            .collect()
+    }
+
+    pub fn leaf(&self, index: usize) -> Option<String> {
+        if index >= self.inserted_elements_amount {
+            return None;
+        }
+        Some(self.node_at(self.first_leaf() + index))
+    }
+
+    /// Each level from the root to the leaves. The padding nodes are included, and with promote padding the missing
+    /// ones are empty strings
+    pub fn levels(&self) -> impl Iterator<Item = Vec<String>> + '_ {
+        let levels = if self.elements.is_empty() {
+            0
+        } else {
+            self.depth() + 1
+        };
+        (0..levels).map(|level| {
+            (0..1 << level)
+                .map(|index| self.node(level, index).unwrap_or_default())
+                .collect()
+        })
+    }
+
+    /// The node at an index of a level, being level 0 the root. Returns None if it's out of the tree or if it's
+    /// missing because of the promote padding
+    pub fn node(&self, level: u32, index: usize) -> Option<String> {
+        if level > self.depth() || index >= 1 << level {
+            return None;
         }
 
-        let log = num.log(2.0);
-        log.fract() == 0.0
+        let pos = (1 << level) - 1 + index;
+        if pos >= self.elements.len() || (self.padding == Padding::Promote && self.is_padding(pos))
+        {
+            return None;
+        }
+        Some(self.node_at(pos))
     }
 
-    /// Decided to insert all the copies to the tree when needed to fill spaces
-    fn insert_hash(&mut self, hashed_string: String) {
-        let non_leaf_nodes =
-            2_usize.pow(f32::log2(self.inserted_elements_amount as f32) as u32 + 1) - 1;
+    pub fn root(&self) -> Option<String> {
+        self.node(0, 0)
+    }
 
-        let gap = non_leaf_nodes - self.inserted_elements_amount;
-        let amount_of_copies = self.elements.len() - self.inserted_elements_amount - non_leaf_nodes;
+    /// Amount of inserted leaves, without the padding
+    pub fn len(&self) -> usize {
+        self.inserted_elements_amount
+    }
 
-        if gap > 0 && amount_of_copies == 0 {
-            // When i do insert and there are spaces left
-            for _ in 0..gap {
-                self.elements.push(hashed_string.clone());
+    pub fn is_empty(&self) -> bool {
+        self.inserted_elements_amount == 0
+    }
+
+    /// The node at a position of the store, taking the nodes with only padding below from padding_hashes
+    fn node_at(&self, pos: usize) -> String {
+        if self.is_padding(pos) {
+            let height = self.depth() - (pos + 1).ilog2();
+            return self.padding_hashes[height as usize].clone();
+        }
+        self.elements.get(pos)
+    }
+
+    /// Hashes a subtree of padding leaves for each height up to the root. With duplicate padding the leaves are
+    /// copies of the last one, so they change on every add
+    fn update_padding_hashes(&mut self) {
+        let leaf = match self.padding {
+            Padding::DuplicateLast => {
+                self.node_at(self.first_leaf() + self.inserted_elements_amount - 1)
             }
-            self.elements.push(hashed_string.clone());
-        } else if gap == 0 {
-            // When i replace the last copy element placed to fill the elements
-            self.elements.pop();
-            self.elements.push(hashed_string);
-        } else if amount_of_copies > 0 {
-            // When i replace copy element placed to fill the elements but it's not the last
-            self.elements.pop();
-            self.elements.insert(
-                non_leaf_nodes + self.inserted_elements_amount,
-                hashed_string,
-            );
+            _ => self.padding_hash(),
+        };
+        self.padding_hashes.clear();
+        self.padding_hashes.push(leaf);
+        for _ in 0..self.depth() {
+            let below = &self.padding_hashes[self.padding_hashes.len() - 1];
+            // With promote padding the subtrees without leaves are missing, at every height
+            let hash = match self.padding {
+                Padding::Promote => below.clone(),
+                _ => self.hash_node(below, below),
+            };
+            self.padding_hashes.push(hash);
         }
+    }
 
-        self.inserted_elements_amount += 1;
+    /// The value the empty leaves take until a real one is inserted
+    fn padding_hash(&self) -> String {
+        match self.padding {
+            Padding::DuplicateLast | Padding::Promote => "".to_string(),
+            Padding::Zero => ZERO_HASH.to_string(),
+        }
     }
 
-    /// The logic is: First, insert the element, and then recalculate the middle hashes
-    fn rehash_tree(&mut self, pos: usize) {
-        // Use is_none bc cargo clippy sugered it instead of an if let
-        if self.elements.get(pos).is_none() {
-            return;
+    /// When depth increase is needed, the current tree becomes the left subtree of a new root, and the right one is
+    /// filled with padding leaves. Every level is moved to its new position in the vector. Returns if it expanded
+    fn expand_tree(&mut self) -> bool {
+        if self.inserted_elements_amount == 0 {
+            // The smallest tree is a root with two leaves
+            self.elements.clear();
+            self.elements.resize(3, &self.padding_hash());
+            return true;
+        }
+        // Needed this bc 1 is power of two and should not execute the logic that is inside the lower if
+        if self.inserted_elements_amount == 1 {
+            return false;
+        }
+        if MerkleTree::number_is_power_of_two(self.inserted_elements_amount) {
+            let padding_hash = self.padding_hash();
+            let new_capacity = 2 * self.inserted_elements_amount;
+            self.elements.resize(2 * new_capacity - 1, &padding_hash);
+
+            // The level i of the old tree is the left half of the level i + 1 of the new one. Moving the lowest
+            // levels first, no level is overwritten before being moved, and then the right halves become padding
+            for level in (0..=self.inserted_elements_amount.trailing_zeros()).rev() {
+                let old_begin = (1 << level) - 1;
+                let new_begin = (2 << level) - 1;
+                let level_nodes = 1 << level;
+                for i in 0..level_nodes {
+                    let node = self.elements.get(old_begin + i);
+                    self.elements.set(new_begin + i, &node);
+                    self.elements
+                        .set(new_begin + level_nodes + i, &padding_hash);
+                }
+            }
+            return true;
         }
+        false
+    }
 
-        // Here i make the lower nodes be hashed before current node makes the hashing. Note that if the following is Null
-        // the error will be catched in the if is_none at the beginning of the function
-        self.rehash_tree(pos + 1);
+    fn number_is_power_of_two(num: usize) -> bool {
+        num.is_power_of_two()
+    }
 
-        // This can be reasoned the following way: If have two sons, my hash is the result of hashing both. If I have only
-        // one, I'll hash it with a copy of itself, and if I dont have sons (I'm a leaf node) y return my own hash
-        let pos_hash = self.elements[pos].clone();
-        let result = match self.elements.get(2 * pos + 1) {
-            Some(hashed_left) => match self.elements.get(2 * pos + 2) {
-                Some(hashed_right) => MerkleTree::combine_hashes(hashed_left, hashed_right),
-                None => hashed_left.to_string(),
-            },
-            None => pos_hash,
-        };
+    /// Places the hash in the first free leaf. The leaves after it are padding, read from padding_hashes
+    fn insert_hash(&mut self, hashed_string: String) {
+        let position = self.first_leaf() + self.inserted_elements_amount;
 
-        self.elements[pos] = result;
+        self.elements.set(position, &hashed_string);
+
+        self.inserted_elements_amount += 1;
+        self.elements.set_leaves_len(self.inserted_elements_amount);
+    }
+
+    /// Returns true if the node has no inserted leaf below it, which can only happen in the padding part of the tree
+    fn is_padding(&self, pos: usize) -> bool {
+        let level = (pos + 1).ilog2();
+        let index_in_level = pos + 1 - (1 << level);
+        let height = self.depth() - level;
+
+        (index_in_level << height) >= self.inserted_elements_amount
+    }
+
+    /// Recalculates the hashes of the ancestors of the leaves from first_changed to last_changed, level by level up to the root
+    fn rehash_tree(&mut self, first_changed: usize, last_changed: usize) {
+        let mut begin = self.first_leaf() + first_changed;
+        let mut end = self.first_leaf() + last_changed;
+
+        while begin > 0 {
+            begin = (begin - 1) / 2;
+            end = (end - 1) / 2;
+
+            for pos in begin..=end {
+                // If have two sons, my hash is the result of hashing both. With promote padding, if my right son
+                // has no inserted leaves below it, I just take the hash of the left one
+                let result = if self.padding == Padding::Promote && self.is_padding(2 * pos + 2) {
+                    self.node_at(2 * pos + 1)
+                } else {
+                    self.hash_node(&self.node_at(2 * pos + 1), &self.node_at(2 * pos + 2))
+                };
+                self.elements.set(pos, &result);
+            }
+        }
     }
 
     /// The logic is: From the leaf, hashing with the proofs I reach my own root and compare it to the original
-    pub fn verify(&self, proof: Vec<String>, leaf: String, index: &mut i32) -> bool {
-        let mut hash = leaf;
+    pub fn verify(&self, proof: Vec<String>, leaf: String, index: usize) -> bool {
+        root_from_proof(
+            &self.domain,
+            self.padding,
+            self.inserted_elements_amount,
+            proof,
+            leaf,
+            index,
+        )
+        .is_some_and(|root| Some(root) == self.root())
+    }
 
-        MerkleTree::generate_root(proof, &mut hash, index);
+    fn sibling_is_padding(&self, index: usize, height: u32) -> bool {
+        sibling_is_missing(self.padding, self.inserted_elements_amount, index, height)
+    }
 
-        hash == self.elements[0]
+    /// Made a similar advance to the verify method, but here I save the sibling instead of rehashing
+    pub fn generate_proof(&self, index: usize) -> Vec<String> {
+        self.proof_positions(index)
+            .into_iter()
+            .map(|(level, index)| self.node_at((1 << level) - 1 + index))
+            .collect()
     }
 
-    /// Here I do the combinations to reach the root
-    fn generate_root(proof: Vec<String>, hash: &mut String, index: &mut i32) {
-        for proof_element in proof {
-            if *index % 2 == 0 {
-                *hash = MerkleTree::combine_hashes(hash, &proof_element);
-            } else {
-                *hash = MerkleTree::combine_hashes(&proof_element, hash);
-            }
+    /// The proof of a leaf together with what is needed to verify it without the tree. None if there is no leaf at
+    /// the index
+    pub fn prove(&self, index: usize) -> Option<MerkleProof> {
+        Some(MerkleProof {
+            index,
+            tree_size: self.len(),
+            leaf: self.leaf(index)?,
+            siblings: self.generate_proof(index),
+        })
+    }
 
-            *index /= 2;
+    /// The proof that the tree of the first old_size leaves grew into this one. None if old_size is 0 or bigger than the
+    /// tree, or if the padding is not promote
+    pub fn prove_consistency(&self, old_size: usize) -> Option<ConsistencyProof> {
+        if self.padding != Padding::Promote || old_size == 0 || old_size > self.len() {
+            return None;
         }
+
+        let mut hashes = Vec::new();
+        self.consistency_subproof(old_size, 0, self.len(), true, &mut hashes);
+        Some(ConsistencyProof {
+            old_size,
+            new_size: self.len(),
+            hashes,
+        })
     }
 
-    /// Made a similar advance to the verify method, but here I save the sibling instead of rehashing
-    pub fn generate_proof(&mut self, index: &mut usize) -> Vec<String> {
-        let mut proof: Vec<String> = Vec::new();
-
-        let non_leaf_nodes =
-            2_i8.pow(f32::log2(self.inserted_elements_amount as f32) as u32) as usize - 1;
-        *index += non_leaf_nodes;
-
-        // raises a never read error, but IMO it's not a real problem
-        #[allow(unused_assignments)]
-        let mut even_offset = 0; // Exists for handling the climbing of the tree to the root
-
-        while *index >= 1 {
-            if *index % 2 == 0 {
-                proof.push(self.elements[*index - 1].clone());
-                even_offset = 1;
-            } else {
-                proof.push(self.elements[*index + 1].clone());
-                even_offset = 0;
+    /// SUBPROOF of RFC 6962 for the leaves from start to end, being the first old_size of them the old tree. While
+    /// the old tree is the whole left edge its root is known by the verifier, so it's not added
+    fn consistency_subproof(
+        &self,
+        old_size: usize,
+        start: usize,
+        end: usize,
+        whole_old_tree: bool,
+        hashes: &mut Vec<String>,
+    ) {
+        let size = end - start;
+        if old_size == size {
+            if !whole_old_tree {
+                hashes.push(self.range_hash(start, end));
             }
+            return;
+        }
 
-            *index = *index / 2 - even_offset;
+        // The largest power of two smaller than the size splits the leaves as the tree does
+        let split = 1 << (size - 1).ilog2();
+        if old_size <= split {
+            self.consistency_subproof(old_size, start, start + split, whole_old_tree, hashes);
+            hashes.push(self.range_hash(start + split, end));
+        } else {
+            self.consistency_subproof(old_size - split, start + split, end, false, hashes);
+            hashes.push(self.range_hash(start, start + split));
         }
+    }
 
-        proof
+    /// The node above the leaves from start to end. They must be the ones of a subtree, which with the promote
+    /// padding can end before its last leaf if the tree does
+    fn range_hash(&self, start: usize, end: usize) -> String {
+        let height = (end - start).next_power_of_two().trailing_zeros();
+        self.subtree_hash(height, start >> height)
+            .expect("the leaves of a subtree have a node above them")
     }
 
-    pub fn print(&self) {
-        let levels = (0..)
-            .take_while(|&n| (1 << n) - 1 < self.elements.len())
-            .count();
-        for i in 0..levels {
-            let level_nodes = 1 << i;
-            let begin = (1 << i) - 1;
-            let end = begin + level_nodes;
-
-            let spaces = (2 << (levels - i - 1)) - 1;
-            print!("{:width$}", "", width = spaces);
-
-            for j in begin..end {
-                if j < self.elements.len() {
-                    print!("{}..  ", self.elements[j].clone().split_at(4).0);
-                }
+    /// (level, index) of the nodes that generate_proof returns for a leaf, from the bottom to the top
+    pub fn proof_positions(&self, index: usize) -> Vec<(u32, usize)> {
+        if index >= self.inserted_elements_amount {
+            return Vec::new();
+        }
+
+        (0..self.depth())
+            .filter(|height| !self.sibling_is_padding(index >> height, *height))
+            .map(|height| (self.depth() - height, (index >> height) ^ 1))
+            .collect()
+    }
+
+    /// Returns true if the node at (level, index) has only padding leaves below it
+    pub fn is_padding_node(&self, level: u32, index: usize) -> bool {
+        self.is_padding((1 << level) - 1 + index)
+    }
+
+    /// Indexes of the leaves that differ between both trees, plus the ones that only the larger tree has, in
+    /// ascending order. Both trees are walked from the root, only going down into the subtrees whose hashes differ,
+    /// so it costs about the amount of differences times the depth. The hashes of the inner nodes are only comparable
+    /// if both trees use the same padding and domain separation; if not, it still works but goes through every leaf
+    pub fn diff(&self, other: &MerkleTree) -> Vec<usize> {
+        let mut differences = Vec::new();
+        self.diff_subtree(other, self.depth().max(other.depth()), 0, &mut differences);
+        differences
+    }
+
+    /// Compares the subtrees over the leaves from index << height to (index + 1) << height in both trees
+    fn diff_subtree(
+        &self,
+        other: &MerkleTree,
+        height: u32,
+        index: usize,
+        differences: &mut Vec<usize>,
+    ) {
+        let shorter = self.len().min(other.len());
+        let longer = self.len().max(other.len());
+        let first = index << height;
+        let last = ((index + 1) << height).min(longer);
+        if first >= longer {
+            return;
+        }
+        if first >= shorter {
+            differences.extend(first..last);
+            return;
+        }
+
+        if height == 0 {
+            if self.leaf(index) != other.leaf(index) {
+                differences.push(index);
             }
-            println!();
+            return;
+        }
+
+        // Subtrees that have padding in one of the trees can have the same hash with different leaves
+        let only_shared_leaves = (index + 1) << height <= shorter;
+        if only_shared_leaves
+            && self.subtree_hash(height, index) == other.subtree_hash(height, index)
+        {
+            return;
         }
+        self.diff_subtree(other, height - 1, 2 * index, differences);
+        self.diff_subtree(other, height - 1, 2 * index + 1, differences);
+    }
+
+    /// The node with the given height over the leaves, None if the tree is not that deep
+    pub(crate) fn subtree_hash(&self, height: u32, index: usize) -> Option<String> {
+        self.node(self.depth().checked_sub(height)?, index)
+    }
+
+    /// Freezes the current version of the tree. If the last snapshot is still alive, only the nodes that changed
+    /// since it are copied and the rest are shared with it. Otherwise every node is copied, which takes as much memory
+    /// as the tree itself: the first time, once the last snapshot was dropped, and after clear, with_leaves,
+    /// with_padding or with_domain_separation, since they change the nodes the old one had.
+    /// The tree doesn't keep its snapshots alive, so they are freed as soon as whoever took them drops them
+    pub fn snapshot(&mut self) -> MerkleSnapshot {
+        let previous = self.last_snapshot.as_ref().and_then(WeakSnapshot::upgrade);
+        let snapshot = snapshot::freeze(self, previous.as_ref());
+        self.last_snapshot = Some(snapshot.downgrade());
+        snapshot
+    }
+
+    #[cfg(feature = "std")]
+    /// Prints the tree with the default options, see printer::render to choose them
+    pub fn print(&self) {
+        print!("{}", printer::render(self, &PrintOptions::default()));
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::MerkleTree;
+    use super::{MerkleTree, Padding, ZERO_HASH};
+    use crate::hashing::{self, DomainSeparation, NodeEncoding};
+
+    const PADDINGS: [Padding; 3] = [Padding::DuplicateLast, Padding::Zero, Padding::Promote];
+
+    /// Shorthands for the hashing without domain separation, used to calculate the expected trees by hand
+    impl MerkleTree {
+        fn hash_text(unhashed_text: &str) -> String {
+            hashing::hash_leaf(&DomainSeparation::default(), unhashed_text.as_bytes())
+        }
+
+        /// All the nodes in level order, padding included
+        fn nodes(&self) -> Vec<String> {
+            (0..self.elements.len())
+                .map(|pos| self.node_at(pos))
+                .collect()
+        }
+
+        fn combine_hashes(hash_left: &str, hash_right: &str) -> String {
+            hashing::hash_node(&DomainSeparation::default(), hash_left, hash_right)
+        }
+    }
 
     #[test]
     fn test_01_tree_is_created_with_valid_args() {
         // Create a MerkleTree and begins with an empty vec
         let tree = MerkleTree::new();
 
-        assert_eq!(0, tree.elements.len());
+        assert_eq!(0, tree.nodes().len());
         assert_eq!(0, tree.inserted_elements_amount);
     }
 
@@ -245,8 +764,8 @@ mod tests {
         let hased_string_1 = MerkleTree::hash_text("Merkle Tree");
         let hashed_string_root = MerkleTree::combine_hashes(&hased_string_0, &hased_string_1);
 
-        assert_eq!(3, tree.elements.len());
-        assert_eq!(hashed_string_root, tree.elements[0]);
+        assert_eq!(3, tree.nodes().len());
+        assert_eq!(hashed_string_root, tree.nodes()[0]);
         // e92a2fd865f0aada3a9b81de2ca576ae627c025dd282fc2be754f9dee4e234fd
     }
 
@@ -262,8 +781,8 @@ mod tests {
 
         let hashed_string_root = MerkleTree::combine_hashes(&hashed_string_0, &hashed_string_1);
 
-        assert_eq!(3, tree.elements.len());
-        assert_eq!(hashed_string_root, tree.elements[0]);
+        assert_eq!(3, tree.nodes().len());
+        assert_eq!(hashed_string_root, tree.nodes()[0]);
         // 5a13e205575dc3d9a374dfe32941511e62f8cf900fb9df59cae9c17bd8b8ce15
     }
 
@@ -287,8 +806,8 @@ mod tests {
 
         let hashed_string_root = MerkleTree::combine_hashes(&hashed_string_0, &hashed_string_1);
 
-        assert_eq!(7, tree.elements.len());
-        assert_eq!(hashed_string_root, tree.elements[0]);
+        assert_eq!(7, tree.nodes().len());
+        assert_eq!(hashed_string_root, tree.nodes()[0]);
         // d28d8deea9f793a014e668ea4050f34dc669cfc6084cd7bf3ba9ccdf62901cbf
     }
 
@@ -312,8 +831,8 @@ mod tests {
 
         let hashed_string_root = MerkleTree::combine_hashes(&hashed_string_0, &hashed_string_1);
 
-        assert_eq!(7, tree.elements.len());
-        assert_eq!(hashed_string_root, tree.elements[0]);
+        assert_eq!(7, tree.nodes().len());
+        assert_eq!(hashed_string_root, tree.nodes()[0]);
         // 8b63c8eebf3c438a9e6aff8c860febfda5d28ab473faa6c6375a01009920b91d
     }
 
@@ -350,8 +869,8 @@ mod tests {
         let hashed_string_1 = MerkleTree::combine_hashes(&hashed_string_10, &hashed_string_11);
         let hashed_string_root = MerkleTree::combine_hashes(&hashed_string_0, &hashed_string_1);
 
-        assert_eq!(15, tree.elements.len());
-        assert_eq!(hashed_string_root, tree.elements[0]);
+        assert_eq!(15, tree.nodes().len());
+        assert_eq!(hashed_string_root, tree.nodes()[0]);
         // 8b63c8eebf3c438a9e6aff8c860febfda5d28ab473faa6c6375a01009920b91d
     }
 
@@ -388,8 +907,8 @@ mod tests {
 
         let hashed_string_root = MerkleTree::combine_hashes(&hashed_string_0, &hashed_string_1);
 
-        assert_eq!(15, tree.elements.len());
-        assert_eq!(hashed_string_root, tree.elements[0]);
+        assert_eq!(15, tree.nodes().len());
+        assert_eq!(hashed_string_root, tree.nodes()[0]);
         // 584d46bf1bfe774bca9d4f620d127a87a2f78a341001f5f644a2f5f153c82cad
     }
 
@@ -455,8 +974,8 @@ mod tests {
 
         let hashed_string_root = MerkleTree::combine_hashes(&hashed_string_0, &hashed_string_1);
 
-        assert_eq!(31, tree.elements.len());
-        assert_eq!(hashed_string_root, tree.elements[0]);
+        assert_eq!(31, tree.nodes().len());
+        assert_eq!(hashed_string_root, tree.nodes()[0]);
         // 7d6aca7ece41a33246a1fe3d13dcf074b701aa43717a19a93047553fc38294b0
     }
 
@@ -494,13 +1013,13 @@ mod tests {
 
     #[test]
     fn test_11_power_of_two_function_works_correctly() {
-        assert!(MerkleTree::number_is_power_of_two(1.));
-        assert!(MerkleTree::number_is_power_of_two(2.));
-        assert!(MerkleTree::number_is_power_of_two(8.));
-        assert!(MerkleTree::number_is_power_of_two(64.));
-        assert!(MerkleTree::number_is_power_of_two(128.));
-        assert!(MerkleTree::number_is_power_of_two(512.));
-        assert!(MerkleTree::number_is_power_of_two(2048.));
+        assert!(MerkleTree::number_is_power_of_two(1));
+        assert!(MerkleTree::number_is_power_of_two(2));
+        assert!(MerkleTree::number_is_power_of_two(8));
+        assert!(MerkleTree::number_is_power_of_two(64));
+        assert!(MerkleTree::number_is_power_of_two(128));
+        assert!(MerkleTree::number_is_power_of_two(512));
+        assert!(MerkleTree::number_is_power_of_two(2048));
     }
 
     #[test]
@@ -518,7 +1037,7 @@ mod tests {
                 "9630101c1c273a6c4714cc7388f35cd7f1b547bf3bc740caf3d943e33e0a9c37".to_string()
             ],
             "cbcbd2ab218ea6a894d3a93e0e83ed0cc0286597a826d3ef4ff3a360e22a7952".to_string(),
-            &mut 0
+            0
         ))
     }
 
@@ -537,7 +1056,7 @@ mod tests {
                 "9630101c1c273a6c4714cc7388f35cd7f1b547bf3bc740caf3d943e33e0a9c37".to_string()
             ],
             "not_a_seed".to_string(),
-            &mut 0
+            0
         ))
     }
 
@@ -560,7 +1079,7 @@ mod tests {
                 "d50c873877f38fcbc56dbe836b9d979912efcb587ed8eea919372d403b5c2bd4".to_string()
             ],
             "3e23e8160039594a33894f6564e1b1348bbd7a0088d42c4acb73eeaed59c009d".to_string(),
-            &mut 1
+            1
         ))
     }
 
@@ -576,38 +1095,38 @@ mod tests {
                 "62af5c3cb8da3e4f25061e829ebeea5c7513c54949115b1acc225930a90154da".to_string()
             ],
             "18ac3e7343f016890c510e93f935261169d9e3f565436429830faf0934f4f8e4".to_string(),
-            &mut 3
+            3
         ))
     }
 
     #[test]
     fn test_16_proof_is_expected_in_a_two_depth_tree() {
         // The proof is the expected in a 2-depth tree
-        let mut tree = MerkleTree::build(vec!["a", "b", "c", "d"], true);
+        let tree = MerkleTree::build(vec!["a", "b", "c", "d"], true);
 
-        println!("{:?}", tree.elements);
+        println!("{:?}", tree.nodes());
         assert_eq!(
             vec![
                 "ca978112ca1bbdcafac231b39a23dc4da786eff8147c4e72b9807785afee48bb".to_string(),
                 "d3a0f1c792ccf7f1708d5422696263e35755a86917ea76ef9242bd4a8cf4891a".to_string()
             ],
-            tree.generate_proof(&mut 1)
+            tree.generate_proof(1)
         );
     }
 
     #[test]
     fn test_17_proof_is_expected_in_a_three_depth_tree() {
         // The proof is the expected in a 3 depth tree
-        let mut tree = MerkleTree::build(vec!["a", "b", "c", "d", "e", "f", "g", "h"], true);
-        let mut index = 1;
-        println!("{:?}", tree.elements);
+        let tree = MerkleTree::build(vec!["a", "b", "c", "d", "e", "f", "g", "h"], true);
+        let index = 1;
+        println!("{:?}", tree.nodes());
         assert_eq!(
             vec![
                 "ca978112ca1bbdcafac231b39a23dc4da786eff8147c4e72b9807785afee48bb".to_string(),
                 "d3a0f1c792ccf7f1708d5422696263e35755a86917ea76ef9242bd4a8cf4891a".to_string(),
                 "d6cf2ad3f66d0599d97346c6aad0f1081913df26d8b80e4ffa052e0a1f8391c6".to_string()
             ],
-            tree.generate_proof(&mut index)
+            tree.generate_proof(index)
         );
     }
 
@@ -632,8 +1151,516 @@ mod tests {
 
         let hashed_string_root = MerkleTree::combine_hashes(&hashed_string_0, &hashed_string_1);
 
-        assert_eq!(7, tree.elements.len());
-        assert_eq!(hashed_string_root, tree.elements[0]);
+        assert_eq!(7, tree.nodes().len());
+        assert_eq!(hashed_string_root, tree.nodes()[0]);
         // c567f133613aac1e0f011569c65daf490adbb87a87db7246ac045b79c64d1460
     }
+
+    #[test]
+    fn test_19_duplicate_padding_makes_a_repeated_last_leaf_indistinguishable() {
+        // This is the ambiguity that makes the other paddings needed
+        let three = MerkleTree::build(vec!["a", "b", "c"], true);
+        let four = MerkleTree::build(vec!["a", "b", "c", "c"], true);
+
+        assert_eq!(Padding::DuplicateLast, three.padding());
+        assert_eq!(three.nodes()[0], four.nodes()[0]);
+    }
+
+    #[test]
+    fn test_20_zero_padding_fills_the_leaves_with_zero_hashes() {
+        let tree = MerkleTree::build(vec!["a", "b", "c"], true).with_padding(Padding::Zero);
+        let four = MerkleTree::build(vec!["a", "b", "c", "c"], true).with_padding(Padding::Zero);
+
+        let hashed_string_0 =
+            MerkleTree::combine_hashes(&MerkleTree::hash_text("a"), &MerkleTree::hash_text("b"));
+        let hashed_string_1 = MerkleTree::combine_hashes(&MerkleTree::hash_text("c"), ZERO_HASH);
+        let hashed_string_root = MerkleTree::combine_hashes(&hashed_string_0, &hashed_string_1);
+
+        assert_eq!(7, tree.nodes().len());
+        assert_eq!(ZERO_HASH, tree.nodes()[6]);
+        assert_eq!(hashed_string_root, tree.nodes()[0]);
+        assert_ne!(four.nodes()[0], tree.nodes()[0]);
+    }
+
+    #[test]
+    fn test_21_promote_padding_takes_the_odd_node_up_unchanged() {
+        let mut tree = MerkleTree::new().with_padding(Padding::Promote);
+        tree.add_unhashed("a".to_string());
+
+        // A single leaf is its own root
+        assert_eq!(MerkleTree::hash_text("a"), tree.nodes()[0]);
+
+        tree.add_unhashed("b".to_string());
+        tree.add_unhashed("c".to_string());
+
+        let hashed_string_0 =
+            MerkleTree::combine_hashes(&MerkleTree::hash_text("a"), &MerkleTree::hash_text("b"));
+        let hashed_string_root =
+            MerkleTree::combine_hashes(&hashed_string_0, &MerkleTree::hash_text("c"));
+
+        assert_eq!(hashed_string_root, tree.nodes()[0]);
+    }
+
+    #[test]
+    fn test_22_every_proof_verifies_with_every_padding() {
+        for padding in PADDINGS {
+            for size in 1..=17 {
+                let texts: Vec<String> = (0..size).map(|i| i.to_string()).collect();
+                let tree = MerkleTree::build(texts.iter().map(|t| t.as_str()).collect(), true)
+                    .with_padding(padding);
+
+                for (i, text) in texts.iter().enumerate() {
+                    let proof = tree.generate_proof(i);
+                    let leaf = MerkleTree::hash_text(text);
+
+                    assert!(tree.verify(proof.clone(), leaf.clone(), i));
+                    // The same proof can't be used for another position
+                    assert!(size == 1 || !tree.verify(proof, leaf, (i + 1) % size));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_23_promote_proofs_dont_include_the_missing_siblings() {
+        let tree =
+            MerkleTree::build(vec!["a", "b", "c", "d", "e"], true).with_padding(Padding::Promote);
+
+        // The fifth leaf has no siblings until the root level
+        assert_eq!(vec![tree.nodes()[1].clone()], tree.generate_proof(4));
+        assert_eq!(3, tree.generate_proof(0).len());
+    }
+
+    #[test]
+    fn test_24_adding_one_by_one_gives_the_same_tree_as_building() {
+        for padding in PADDINGS {
+            let texts = ["a", "b", "c", "d", "e", "f", "g", "h", "i", "j"];
+            let mut tree = MerkleTree::new().with_padding(padding);
+
+            for (i, text) in texts.iter().enumerate() {
+                tree.add_unhashed(text.to_string());
+
+                let built = MerkleTree::build(texts[..=i].to_vec(), true).with_padding(padding);
+                assert_eq!(built.nodes(), tree.nodes());
+            }
+        }
+    }
+
+    #[test]
+    fn test_25_proofs_work_past_a_hundred_and_twenty_eight_leaves() {
+        let texts: Vec<String> = (0..300).map(|i| i.to_string()).collect();
+        let tree = MerkleTree::build(texts.iter().map(|t| t.as_str()).collect(), true);
+
+        let proof = tree.generate_proof(257);
+
+        assert_eq!(9, proof.len());
+        assert!(tree.verify(proof, MerkleTree::hash_text("257"), 257));
+    }
+
+    #[test]
+    fn test_26_inner_nodes_cant_be_passed_as_leaves_with_domain_separation() {
+        // Without tags, a tree whose leaves are the concatenated hashes of the lower level has the same root
+        let hashes: Vec<String> = ["a", "b", "c", "d"]
+            .iter()
+            .map(|text| MerkleTree::hash_text(text))
+            .collect();
+        let forged_leaves = [
+            hashes[0].clone() + &hashes[1],
+            hashes[2].clone() + &hashes[3],
+        ];
+
+        let tree = MerkleTree::build(vec!["a", "b", "c", "d"], true);
+        let forged = MerkleTree::build(forged_leaves.iter().map(|l| l.as_str()).collect(), true);
+        assert_eq!(tree.nodes()[0], forged.nodes()[0]);
+
+        let mut tree = MerkleTree::new().with_domain_separation(DomainSeparation::rfc6962());
+        let mut forged = MerkleTree::new().with_domain_separation(DomainSeparation::rfc6962());
+        for text in ["a", "b", "c", "d"] {
+            tree.add_unhashed(text.to_string());
+        }
+        for leaf in forged_leaves {
+            forged.add_unhashed(leaf);
+        }
+        assert_ne!(tree.nodes()[0], forged.nodes()[0]);
+    }
+
+    #[test]
+    fn test_27_proofs_verify_with_domain_separation() {
+        let domain = DomainSeparation::new("leaf:", "node:").unwrap();
+        let mut tree = MerkleTree::new().with_domain_separation(domain.clone());
+        for text in ["a", "b", "c", "d", "e"] {
+            tree.add_unhashed(text.to_string());
+        }
+
+        let leaf = hashing::hash_leaf(&domain, b"e");
+        let proof = tree.generate_proof(4);
+
+        assert_eq!(leaf, tree.nodes()[11]);
+        assert!(tree.verify(proof, leaf, 4));
+        assert_ne!(
+            MerkleTree::build(vec!["a", "b", "c", "d", "e"], true).nodes()[0],
+            tree.nodes()[0]
+        );
+    }
+
+    #[test]
+    fn test_28_proofs_with_a_wrong_length_or_index_are_refused() {
+        let tree = MerkleTree::build(vec!["a", "b", "c", "d"], true);
+        let leaf = MerkleTree::hash_text("b");
+        let proof = tree.generate_proof(1);
+
+        // The root itself is a valid proof of length zero for the node it comes from, but not for a leaf
+        assert!(!tree.verify(vec![], tree.nodes()[0].clone(), 0));
+
+        let mut shorter = proof.clone();
+        shorter.pop();
+        assert!(!tree.verify(shorter, tree.nodes()[1].clone(), 0));
+
+        let mut longer = proof.clone();
+        longer.push(tree.nodes()[0].clone());
+        assert!(!tree.verify(longer, leaf.clone(), 1));
+
+        assert!(!tree.verify(proof.clone(), leaf.clone(), 5));
+        assert!(tree.verify(proof, leaf, 1));
+    }
+
+    #[test]
+    fn test_29_leaves_are_found_with_and_without_the_leaf_index() {
+        let without_index = MerkleTree::build(vec!["a", "b", "c"], true);
+        let mut with_index = MerkleTree::new().with_leaf_index();
+        for text in ["a", "b", "c"] {
+            with_index.add_unhashed(text.to_string());
+        }
+
+        for tree in [without_index, with_index] {
+            assert_eq!(Some(0), tree.index_of(&MerkleTree::hash_text("a")));
+            assert_eq!(Some(2), tree.index_of_unhashed("c"));
+            assert!(tree.contains(&MerkleTree::hash_text("b")));
+            assert!(!tree.contains(&MerkleTree::hash_text("d")));
+            assert_eq!(None, tree.index_of_unhashed("d"));
+        }
+    }
+
+    #[test]
+    fn test_30_duplicated_leaves_return_every_index() {
+        // The padding copies of the last leaf are not counted as leaves
+        let tree = MerkleTree::build(vec!["a", "b", "a", "c", "a"], true).with_leaf_index();
+
+        assert_eq!(vec![0, 2, 4], tree.indices_of(&MerkleTree::hash_text("a")));
+        assert_eq!(Some(0), tree.index_of_unhashed("a"));
+
+        let tree = MerkleTree::build(vec!["a", "b", "c"], true);
+        assert_eq!(vec![2], tree.indices_of(&MerkleTree::hash_text("c")));
+    }
+
+    #[test]
+    fn test_31_leaf_index_follows_the_changes_of_the_tree() {
+        let mut tree = MerkleTree::new().with_leaf_index();
+        tree.add_unhashed("a".to_string());
+        tree = tree.with_padding(Padding::Promote);
+        tree.add_unhashed("b".to_string());
+
+        assert_eq!(Some(1), tree.index_of_unhashed("b"));
+
+        tree.clear();
+        assert!(!tree.contains(&MerkleTree::hash_text("a")));
+
+        tree.add_unhashed("b".to_string());
+        assert_eq!(vec![0], tree.indices_of(&MerkleTree::hash_text("b")));
+    }
+
+    #[test]
+    fn test_32_unhashed_lookups_use_the_domain_separation() {
+        let tree = MerkleTree::new().with_domain_separation(DomainSeparation::rfc6962());
+        let mut tree = tree.with_leaf_index();
+        tree.add_unhashed("a".to_string());
+
+        assert_eq!(Some(0), tree.index_of_unhashed("a"));
+        assert!(!tree.contains(&MerkleTree::hash_text("a")));
+    }
+
+    #[test]
+    fn test_33_empty_tree_has_nothing_to_inspect() {
+        let tree = MerkleTree::new();
+
+        assert!(tree.is_empty());
+        assert_eq!(0, tree.len());
+        assert_eq!(0, tree.depth());
+        assert_eq!(None, tree.root());
+        assert_eq!(0, tree.levels().count());
+        assert!(tree.leaves().is_empty());
+    }
+
+    #[test]
+    fn test_34_leaves_dont_include_the_padding() {
+        let tree = MerkleTree::build(vec!["a", "b", "c"], true);
+
+        assert_eq!(3, tree.len());
+        assert_eq!(
+            vec![
+                MerkleTree::hash_text("a"),
+                MerkleTree::hash_text("b"),
+                MerkleTree::hash_text("c")
+            ],
+            tree.leaves()
+        );
+    }
+
+    #[test]
+    fn test_35_levels_go_from_the_root_to_the_leaves() {
+        let tree = MerkleTree::build(vec!["a", "b", "c", "d", "e"], true);
+        let levels: Vec<Vec<String>> = tree.levels().collect();
+
+        assert_eq!(3, tree.depth());
+        assert_eq!(4, levels.len());
+        assert_eq!(
+            vec![1, 2, 4, 8],
+            levels.iter().map(|l| l.len()).collect::<Vec<_>>()
+        );
+        assert_eq!(tree.root().unwrap(), levels[0][0]);
+        assert_eq!(MerkleTree::hash_text("e"), levels[3][7]);
+    }
+
+    #[test]
+    fn test_36_nodes_are_accessed_by_level_and_index() {
+        let tree = MerkleTree::build(vec!["a", "b", "c"], true);
+
+        let hashed_string_0 =
+            MerkleTree::combine_hashes(&MerkleTree::hash_text("a"), &MerkleTree::hash_text("b"));
+        assert_eq!(Some(hashed_string_0), tree.node(1, 0));
+        assert_eq!(Some(MerkleTree::hash_text("c")), tree.node(2, 3));
+        assert_eq!(None, tree.node(2, 4));
+        assert_eq!(None, tree.node(3, 0));
+
+        // With promote padding the missing nodes are not returned
+        let tree = tree.with_padding(Padding::Promote);
+        assert_eq!(Some(MerkleTree::hash_text("c")), tree.node(1, 1));
+        assert_eq!(None, tree.node(2, 3));
+    }
+
+    #[test]
+    fn test_37_proof_positions_point_to_the_proof_nodes() {
+        for padding in PADDINGS {
+            let tree = MerkleTree::build(vec!["a", "b", "c", "d", "e"], true).with_padding(padding);
+
+            for index in 0..5 {
+                let nodes: Vec<String> = tree
+                    .proof_positions(index)
+                    .into_iter()
+                    .map(|(level, i)| tree.node(level, i).unwrap())
+                    .collect();
+
+                assert_eq!(tree.generate_proof(index), nodes);
+            }
+        }
+    }
+
+    #[test]
+    fn test_38_padding_nodes_are_the_ones_without_inserted_leaves() {
+        let tree = MerkleTree::build(vec!["a", "b", "c", "d", "e"], true);
+
+        assert!(!tree.is_padding_node(0, 0));
+        assert!(!tree.is_padding_node(1, 1));
+        assert!(!tree.is_padding_node(3, 4));
+        assert!(tree.is_padding_node(3, 5));
+        assert!(tree.is_padding_node(2, 3));
+    }
+
+    fn tree_of(texts: &[String], padding: Padding) -> MerkleTree {
+        MerkleTree::build(texts.iter().map(|t| t.as_str()).collect(), true).with_padding(padding)
+    }
+
+    #[test]
+    fn test_39_diff_finds_the_changed_leaves() {
+        for padding in PADDINGS {
+            let texts: Vec<String> = (0..100).map(|i| i.to_string()).collect();
+            let tree = tree_of(&texts, padding);
+            assert!(tree.diff(&tree_of(&texts, padding)).is_empty());
+
+            let mut changed = texts.clone();
+            for i in [0, 37, 38, 99] {
+                changed[i] = format!("changed {i}");
+            }
+            let other = tree_of(&changed, padding);
+            assert_eq!(vec![0, 37, 38, 99], tree.diff(&other));
+            assert_eq!(vec![0, 37, 38, 99], other.diff(&tree));
+        }
+    }
+
+    #[test]
+    fn test_40_diff_includes_the_leaves_only_in_the_larger_tree() {
+        for padding in PADDINGS {
+            let texts: Vec<String> = (0..20).map(|i| i.to_string()).collect();
+            let tree = tree_of(&texts, padding);
+
+            let mut longer = texts.clone();
+            longer[3] = "changed".to_string();
+            longer.extend((20..40).map(|i| i.to_string()));
+            let expected: Vec<usize> = std::iter::once(3).chain(20..40).collect();
+            assert_eq!(expected, tree.diff(&tree_of(&longer, padding)));
+            assert_eq!(expected, tree_of(&longer, padding).diff(&tree));
+
+            assert_eq!((0..20).collect::<Vec<_>>(), tree.diff(&MerkleTree::new()));
+            assert!(MerkleTree::new().diff(&MerkleTree::new()).is_empty());
+        }
+
+        // The duplicated leaf has the same root, but the second tree really has one more leaf
+        let three = MerkleTree::build(vec!["a", "b", "c"], true);
+        let four = MerkleTree::build(vec!["a", "b", "c", "c"], true);
+        assert_eq!(three.root(), four.root());
+        assert_eq!(vec![3], three.diff(&four));
+    }
+
+    #[test]
+    fn test_41_trees_built_with_leaves_keep_their_store_and_domain() {
+        let domain = DomainSeparation::rfc6962();
+        let mut added = MerkleTree::new().with_domain_separation(domain.clone());
+        for text in ["a", "b", "c", "d", "e"] {
+            added.add_unhashed(text.to_string());
+        }
+
+        let built = MerkleTree::new()
+            .with_domain_separation(domain)
+            .with_leaves(added.leaves());
+        assert_eq!(added.root(), built.root());
+        assert_eq!(5, built.len());
+        assert!(MerkleTree::new().with_leaves(Vec::new()).is_empty());
+
+        // The leaf index is rebuilt with the new leaves, the old ones are gone
+        let mut indexed = MerkleTree::build(vec!["x", "a"], true).with_leaf_index();
+        indexed = indexed.with_leaves(added.leaves());
+        assert_eq!(Some(0), indexed.index_of(&added.leaves()[0]));
+        assert_eq!(Some(4), indexed.index_of(&added.leaves()[4]));
+        assert!(!indexed.contains(&MerkleTree::hash_text("x")));
+        assert!(MerkleTree::new()
+            .with_leaf_index()
+            .with_leaves(Vec::new())
+            .indices_of(&added.leaves()[0])
+            .is_empty());
+    }
+
+    #[test]
+    fn test_42_consistency_proofs_verify_every_older_tree() {
+        let domain = DomainSeparation::rfc6962().with_node_encoding(NodeEncoding::Raw);
+        let mut tree = MerkleTree::new()
+            .with_padding(Padding::Promote)
+            .with_domain_separation(domain.clone());
+        let mut roots = Vec::new();
+        for i in 0..20 {
+            tree.add_unhashed(i.to_string());
+            roots.push(tree.root().unwrap());
+        }
+        let new_root = tree.root().unwrap();
+
+        for old_size in 1..=20 {
+            let proof = tree.prove_consistency(old_size).unwrap();
+            let old_root = &roots[old_size - 1];
+            assert!(proof.verify(old_root, &new_root, &domain));
+
+            let other_root = &roots[old_size % 20];
+            if other_root != old_root {
+                assert!(!proof.verify(other_root, &new_root, &domain));
+            }
+            if let Some(hash) = proof.hashes.first() {
+                let mut tampered = proof.clone();
+                tampered.hashes[0] = hashing::hash_leaf(&domain, hash.as_bytes());
+                assert!(!tampered.verify(old_root, &new_root, &domain));
+            }
+        }
+        assert_eq!(None, tree.prove_consistency(0));
+        assert_eq!(None, tree.prove_consistency(21));
+    }
+
+    #[test]
+    fn test_43_only_promote_trees_have_consistency_proofs() {
+        for padding in [Padding::DuplicateLast, Padding::Zero] {
+            let tree = MerkleTree::build(vec!["a", "b", "c"], true).with_padding(padding);
+            assert_eq!(None, tree.prove_consistency(2));
+        }
+    }
+
+    #[cfg(feature = "blake3")]
+    #[test]
+    fn test_44_blake3_trees_prove_their_leaves() {
+        use crate::hashing::HashFunction;
+
+        let keyed = DomainSeparation::blake3_keyed([1; 32], [2; 32]).unwrap();
+        let plain = DomainSeparation::default().with_hash_function(HashFunction::Blake3);
+        for domain in [plain, keyed] {
+            for padding in PADDINGS {
+                let mut tree = MerkleTree::new()
+                    .with_padding(padding)
+                    .with_domain_separation(domain.clone());
+                for text in ["a", "b", "c", "d", "e"] {
+                    tree.add_unhashed(text.to_string());
+                }
+                let root = tree.root().unwrap();
+                assert_eq!(hashing::hash_leaf(&domain, b"a"), tree.leaf(0).unwrap());
+                assert_ne!(
+                    MerkleTree::build(vec!["a", "b", "c", "d", "e"], true)
+                        .with_padding(padding)
+                        .root(),
+                    Some(root.clone())
+                );
+
+                for index in 0..5 {
+                    let proof = tree.prove(index).unwrap();
+                    assert!(proof.verify(&root, padding, &domain));
+                    assert!(!proof.verify(&root, padding, &DomainSeparation::default()));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_45_many_adds_give_the_same_tree_as_with_leaves() {
+        let leaves: Vec<String> = (0..3000)
+            .map(|i| MerkleTree::hash_text(&i.to_string()))
+            .collect();
+        for padding in PADDINGS {
+            let mut added = MerkleTree::new().with_padding(padding);
+            for (i, leaf) in leaves.iter().enumerate() {
+                added.add(leaf.clone());
+                let size = i + 1;
+                if size.is_power_of_two() || (size - 1).is_power_of_two() || size % 999 == 0 {
+                    let built = MerkleTree::new()
+                        .with_padding(padding)
+                        .with_leaves(leaves[..size].to_vec());
+                    assert_eq!(built.root(), added.root(), "{padding:?} {size}");
+                }
+            }
+
+            let built = MerkleTree::new()
+                .with_padding(padding)
+                .with_leaves(leaves.clone());
+            assert!(built.levels().eq(added.levels()));
+            for index in [0, 1500, 2047, 2048, 2999] {
+                assert_eq!(built.generate_proof(index), added.generate_proof(index));
+            }
+        }
+    }
+
+    #[test]
+    fn test_46_extending_gives_the_same_tree_as_adding() {
+        let leaves: Vec<String> = (0..40)
+            .map(|i| MerkleTree::hash_text(&i.to_string()))
+            .collect();
+        for padding in PADDINGS {
+            for (first, second) in [(0, 1), (1, 2), (3, 20), (4, 40), (16, 17), (0, 40)] {
+                let mut added = MerkleTree::new().with_padding(padding);
+                let mut extended = MerkleTree::new().with_padding(padding).with_leaf_index();
+                for leaf in &leaves[..second] {
+                    added.add(leaf.clone());
+                }
+                extended.extend(leaves[..first].to_vec());
+                extended.extend(leaves[first..second].to_vec());
+
+                assert!(
+                    added.levels().eq(extended.levels()),
+                    "{padding:?} {first} {second}"
+                );
+                assert_eq!(Some(second - 1), extended.index_of(&leaves[second - 1]));
+            }
+        }
+    }
 }
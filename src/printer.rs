@@ -0,0 +1,393 @@
+use std::collections::HashSet;
+
+use crate::merkle_tree::MerkleTree;
+
+/// Wider than this, the horizontal layout doesn't fit a terminal and the automatic one switches to vertical
+const MAX_HORIZONTAL_WIDTH: usize = 160;
+
+/// Wider than this, even an explicit horizontal layout is rendered vertically: every line is padded to the full width,
+/// so deep trees would take gigabytes
+const MAX_FORCED_HORIZONTAL_WIDTH: usize = 1 << 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Layout {
+    /// Horizontal if it fits in a terminal, vertical if not
+    #[default]
+    Auto,
+    /// Each level in a line, with the root on top. Trees too wide to print like this are printed vertically anyway
+    Horizontal,
+    /// One node per line, indented by level, like the output of the `tree` command
+    Vertical,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrintOptions {
+    /// Amount of characters shown of each hash, None to show them complete
+    pub hash_width: Option<usize>,
+    pub layout: Layout,
+    /// Amount of levels shown from the root, None to show all of them
+    pub max_levels: Option<u32>,
+    /// Leaf whose path to the root is marked with [] and its proof with ()
+    pub highlight: Option<usize>,
+}
+
+impl Default for PrintOptions {
+    fn default() -> Self {
+        PrintOptions {
+            hash_width: Some(4),
+            layout: Layout::Auto,
+            max_levels: None,
+            highlight: None,
+        }
+    }
+}
+
+/// Renders the tree as text. Nodes missing because of the promote padding are shown as a dash
+pub fn render(tree: &MerkleTree, options: &PrintOptions) -> String {
+    if tree.is_empty() {
+        return "The tree is empty\n".to_string();
+    }
+
+    let highlights = Highlights::new(tree, options.highlight);
+    let levels = options
+        .max_levels
+        .unwrap_or(u32::MAX)
+        .clamp(1, tree.depth() + 1);
+
+    let horizontal_width = 1usize.checked_shl(levels - 1).map_or(usize::MAX, |nodes| {
+        nodes.saturating_mul(cell_width(tree, options, levels))
+    });
+    match options.layout {
+        Layout::Horizontal if horizontal_width <= MAX_FORCED_HORIZONTAL_WIDTH => {
+            render_horizontal(tree, options, &highlights, levels)
+        }
+        Layout::Auto if horizontal_width <= MAX_HORIZONTAL_WIDTH => {
+            render_horizontal(tree, options, &highlights, levels)
+        }
+        _ => render_vertical(tree, options, &highlights, levels),
+    }
+}
+
+/// Positions of the path to the root and of the proof of the highlighted leaf
+struct Highlights {
+    path: HashSet<(u32, usize)>,
+    proof: HashSet<(u32, usize)>,
+}
+
+impl Highlights {
+    fn new(tree: &MerkleTree, highlight: Option<usize>) -> Self {
+        let mut highlights = Highlights {
+            path: HashSet::new(),
+            proof: HashSet::new(),
+        };
+
+        if let Some(index) = highlight.filter(|index| *index < tree.len()) {
+            highlights.path = (0..=tree.depth())
+                .map(|height| (tree.depth() - height, index >> height))
+                .collect();
+            highlights.proof = tree.proof_positions(index).into_iter().collect();
+        }
+
+        highlights
+    }
+
+    fn label(&self, tree: &MerkleTree, options: &PrintOptions, level: u32, index: usize) -> String {
+        let text = match tree.node(level, index) {
+            Some(hash) => shorten(&hash, options.hash_width),
+            None => "-".to_string(),
+        };
+
+        if self.path.contains(&(level, index)) {
+            format!("[{text}]")
+        } else if self.proof.contains(&(level, index)) {
+            format!("({text})")
+        } else {
+            format!(" {text} ")
+        }
+    }
+}
+
+/// Cuts by characters, as the leaves added with add can be any text
+fn shorten(hash: &str, hash_width: Option<usize>) -> String {
+    match hash_width {
+        Some(width) if width < hash.chars().count() => {
+            format!("{}..", hash.chars().take(width).collect::<String>())
+        }
+        _ => hash.to_string(),
+    }
+}
+
+/// Width of the widest shortened node of the printed levels, plus the brackets and a space between nodes
+fn cell_width(tree: &MerkleTree, options: &PrintOptions, levels: u32) -> usize {
+    let text_len = (0..levels)
+        .flat_map(|level| (0..1usize << level).map(move |index| (level, index)))
+        .filter_map(|(level, index)| tree.node(level, index))
+        .map(|hash| shorten(&hash, options.hash_width).chars().count())
+        .max()
+        .unwrap_or(1);
+    text_len + 3
+}
+
+/// Each node is centered over the space its children take in the lowest printed level
+fn render_horizontal(
+    tree: &MerkleTree,
+    options: &PrintOptions,
+    highlights: &Highlights,
+    levels: u32,
+) -> String {
+    let cell = cell_width(tree, options, levels);
+    let mut output = String::new();
+
+    for level in 0..levels {
+        let span = cell << (levels - 1 - level);
+        let mut line = String::new();
+        for index in 0..(1 << level) {
+            let label = highlights.label(tree, options, level, index);
+            line.push_str(&format!("{label:^span$}"));
+        }
+        output.push_str(line.trim_end());
+        output.push('\n');
+    }
+
+    if levels <= tree.depth() {
+        output.push_str(&format!("({} more levels)\n", tree.depth() + 1 - levels));
+    }
+
+    output
+}
+
+fn render_vertical(
+    tree: &MerkleTree,
+    options: &PrintOptions,
+    highlights: &Highlights,
+    levels: u32,
+) -> String {
+    let mut output = String::new();
+    write_vertical_node(
+        tree,
+        options,
+        highlights,
+        levels,
+        (0, 0),
+        "",
+        "",
+        &mut output,
+    );
+    output
+}
+
+#[allow(clippy::too_many_arguments)]
+fn write_vertical_node(
+    tree: &MerkleTree,
+    options: &PrintOptions,
+    highlights: &Highlights,
+    levels: u32,
+    (level, index): (u32, usize),
+    prefix: &str,
+    children_prefix: &str,
+    output: &mut String,
+) {
+    output.push_str(prefix);
+    output.push_str(highlights.label(tree, options, level, index).trim());
+    if level == tree.depth() {
+        if index < tree.len() {
+            output.push_str(&format!(" leaf {index}"));
+        } else {
+            output.push_str(" padding");
+        }
+    } else if level + 1 == levels {
+        output.push_str(" ...");
+    }
+    output.push('\n');
+
+    if level + 1 >= levels {
+        return;
+    }
+
+    for (child, last) in [(2 * index, false), (2 * index + 1, true)] {
+        let (branch, continuation) = if last {
+            ("└── ", "    ")
+        } else {
+            ("├── ", "│   ")
+        };
+        write_vertical_node(
+            tree,
+            options,
+            highlights,
+            levels,
+            (level + 1, child),
+            &format!("{children_prefix}{branch}"),
+            &format!("{children_prefix}{continuation}"),
+            output,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{render, Layout, PrintOptions};
+    use crate::merkle_tree::{MerkleTree, Padding};
+
+    fn tree_of(size: usize) -> MerkleTree {
+        let texts: Vec<String> = (0..size).map(|i| i.to_string()).collect();
+        MerkleTree::build(texts.iter().map(|t| t.as_str()).collect(), true)
+    }
+
+    #[test]
+    fn test_01_horizontal_layout_centers_each_level() {
+        let tree = MerkleTree::build(vec!["a", "b", "c"], true);
+        let options = PrintOptions {
+            layout: Layout::Horizontal,
+            ..PrintOptions::default()
+        };
+
+        assert_eq!(
+            "               0bdf..\n      62af..            d50c..\n ca97..   3e23..   2e7d..   2e7d..\n",
+            render(&tree, &options)
+        );
+    }
+
+    #[test]
+    fn test_02_vertical_layout_has_a_node_per_line() {
+        let tree = MerkleTree::build(vec!["a", "b", "c"], true);
+        let options = PrintOptions {
+            layout: Layout::Vertical,
+            ..PrintOptions::default()
+        };
+
+        assert_eq!(
+            "0bdf..\n├── 62af..\n│   ├── ca97.. leaf 0\n│   └── 3e23.. leaf 1\n└── d50c..\n    ├── 2e7d.. leaf 2\n    └── 2e7d.. padding\n",
+            render(&tree, &options)
+        );
+    }
+
+    #[test]
+    fn test_03_deep_trees_switch_to_vertical_layout() {
+        let tree = tree_of(5000);
+        let rendered = render(&tree, &PrintOptions::default());
+
+        assert_eq!(2 * 8192 - 1, rendered.lines().count());
+        assert!(rendered.lines().all(|line| line.chars().count() < 80));
+    }
+
+    #[test]
+    fn test_04_level_limit_cuts_the_lower_levels() {
+        let tree = tree_of(5000);
+        let options = PrintOptions {
+            max_levels: Some(3),
+            ..PrintOptions::default()
+        };
+        let rendered = render(&tree, &options);
+
+        assert_eq!(4, rendered.lines().count());
+        assert_eq!(Some("(11 more levels)"), rendered.lines().last());
+
+        let options = PrintOptions {
+            layout: Layout::Vertical,
+            ..options
+        };
+        assert_eq!(7, render(&tree, &options).lines().count());
+    }
+
+    #[test]
+    fn test_05_full_hashes_can_be_shown() {
+        let tree = tree_of(2);
+        let options = PrintOptions {
+            hash_width: None,
+            ..PrintOptions::default()
+        };
+
+        assert!(render(&tree, &options).contains(&tree.root().unwrap()));
+    }
+
+    #[test]
+    fn test_06_proof_of_a_leaf_is_highlighted() {
+        let tree = MerkleTree::build(vec!["a", "b", "c", "d"], true);
+        let options = PrintOptions {
+            highlight: Some(1),
+            layout: Layout::Horizontal,
+            ..PrintOptions::default()
+        };
+        let rendered = render(&tree, &options);
+        let lines: Vec<&str> = rendered.lines().collect();
+
+        assert!(lines[0].contains("[58c8..]"));
+        assert!(lines[1].contains("[62af..]") && lines[1].contains("(d3a0..)"));
+        assert!(lines[2].contains("(ca97..)") && lines[2].contains("[3e23..]"));
+    }
+
+    #[test]
+    fn test_07_missing_and_short_nodes_dont_panic() {
+        let tree = MerkleTree::build(vec!["a", "b", "c"], true).with_padding(Padding::Promote);
+        let rendered = render(&tree, &PrintOptions::default());
+        assert!(rendered.lines().last().unwrap().ends_with(" -"));
+
+        let mut tree = MerkleTree::new();
+        tree.add("ab".to_string());
+        assert!(render(&tree, &PrintOptions::default()).contains(" ab "));
+
+        assert_eq!(
+            "The tree is empty\n",
+            render(&MerkleTree::new(), &PrintOptions::default())
+        );
+    }
+
+    #[test]
+    fn test_08_multibyte_leaves_are_cut_by_characters() {
+        let mut tree = MerkleTree::new();
+        tree.add("aññ".to_string());
+        tree.add("añ".to_string());
+
+        for layout in [Layout::Horizontal, Layout::Vertical] {
+            let options = PrintOptions {
+                hash_width: Some(2),
+                layout,
+                ..PrintOptions::default()
+            };
+            let rendered = render(&tree, &options);
+            assert!(rendered.contains("añ.."));
+            // The short leaf is printed whole
+            assert!(rendered
+                .lines()
+                .flat_map(|line| line.split_whitespace())
+                .any(|label| label.trim_matches(|c| "-[]()".contains(c)) == "añ"));
+        }
+
+        // The cells are as wide as the widest node, even if it is a leaf longer than the root
+        let options = PrintOptions {
+            hash_width: None,
+            layout: Layout::Horizontal,
+            ..PrintOptions::default()
+        };
+        let mut wide = MerkleTree::new();
+        wide.add("ñ".repeat(100));
+        wide.add("b".to_string());
+        let leaves = render(&wide, &options).lines().nth(1).unwrap().to_string();
+        let cell = 100 + 3;
+        assert!(leaves.contains(&"ñ".repeat(100)));
+        assert_eq!(Some(cell + cell / 2), leaves.chars().position(|c| c == 'b'));
+    }
+
+    #[test]
+    fn test_09_trees_too_wide_are_printed_vertically_even_if_asked_horizontal() {
+        let tree = tree_of(5000);
+        let options = PrintOptions {
+            layout: Layout::Horizontal,
+            ..PrintOptions::default()
+        };
+
+        let rendered = render(&tree, &options);
+        assert_eq!(2 * 8192 - 1, rendered.lines().count());
+        assert!(rendered.lines().all(|line| line.chars().count() < 80));
+
+        let rendered = render(
+            &tree,
+            &PrintOptions {
+                max_levels: Some(6),
+                ..options
+            },
+        );
+        assert_eq!(7, rendered.lines().count());
+        assert!(rendered.ends_with("(8 more levels)\n"));
+    }
+}
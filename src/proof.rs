@@ -0,0 +1,306 @@
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use crate::hashing::{self, DomainSeparation};
+use crate::merkle_tree::Padding;
+
+/// Everything needed to check that a leaf is in a tree without having the tree: its index, the size of the tree
+/// (with promote padding it decides which levels have a sibling) and the siblings from the bottom to the top
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MerkleProof {
+    pub index: usize,
+    pub tree_size: usize,
+    pub leaf: String,
+    pub siblings: Vec<String>,
+}
+
+impl MerkleProof {
+    /// The root the proof leads to, None if the index is out of the tree or it has a wrong amount of siblings. The
+    /// padding and domain separation must be the ones of the tree
+    pub fn root(&self, padding: Padding, domain: &DomainSeparation) -> Option<String> {
+        root_from_proof(
+            domain,
+            padding,
+            self.tree_size,
+            self.siblings.clone(),
+            self.leaf.clone(),
+            self.index,
+        )
+    }
+
+    pub fn verify(&self, root: &str, padding: Padding, domain: &DomainSeparation) -> bool {
+        self.root(padding, domain)
+            .is_some_and(|proof_root| proof_root == root)
+    }
+}
+
+/// A proof with the hashes as bytes, for verifiers that can't allocate (firmware checking an update manifest, for
+/// example). The siblings can live in a fixed size array
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FixedProof<'a> {
+    pub index: usize,
+    pub tree_size: usize,
+    pub leaf: [u8; 32],
+    pub siblings: &'a [[u8; 32]],
+}
+
+impl FixedProof<'_> {
+    /// The domain separation can be made once at startup, the leaf is already hashed so only the nodes use it
+    pub fn root(&self, padding: Padding, domain: &DomainSeparation) -> Option<[u8; 32]> {
+        climb(
+            padding,
+            self.tree_size,
+            self.index,
+            self.leaf,
+            self.siblings.iter().copied(),
+            |left, right| hashing::hash_node_bytes(domain, left, right),
+        )
+    }
+
+    pub fn verify(&self, root: &[u8; 32], padding: Padding, domain: &DomainSeparation) -> bool {
+        self.root(padding, domain)
+            .is_some_and(|proof_root| &proof_root == root)
+    }
+}
+
+/// Shows that a tree of old_size leaves is the beginning of the one of new_size leaves, so leaves were only appended
+/// (RFC 6962, section 2.1.2). Only trees with the promote padding have them, the only one where adding leaves keeps
+/// the older subtrees as they were
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConsistencyProof {
+    pub old_size: usize,
+    pub new_size: usize,
+    pub hashes: Vec<String>,
+}
+
+impl ConsistencyProof {
+    /// Checks the proof against the roots of both trees, as RFC 9162 (section 2.1.4.2) does. The domain separation
+    /// must be the one of the trees
+    pub fn verify(&self, old_root: &str, new_root: &str, domain: &DomainSeparation) -> bool {
+        if self.old_size == 0 || self.old_size > self.new_size {
+            return false;
+        }
+        if self.old_size == self.new_size {
+            return self.hashes.is_empty() && old_root == new_root;
+        }
+
+        // When the old tree is a complete subtree of the new one, its root is where both climbs start
+        let mut hashes = self.hashes.iter().map(String::as_str);
+        let first = if self.old_size.is_power_of_two() {
+            old_root
+        } else {
+            match hashes.next() {
+                Some(first) => first,
+                None => return false,
+            }
+        };
+
+        let mut old_node = self.old_size - 1;
+        let mut new_node = self.new_size - 1;
+        while !old_node.is_multiple_of(2) {
+            old_node >>= 1;
+            new_node >>= 1;
+        }
+
+        let mut old_hash = first.to_string();
+        let mut new_hash = first.to_string();
+        for hash in hashes {
+            if new_node == 0 {
+                return false;
+            }
+            if !old_node.is_multiple_of(2) || old_node == new_node {
+                old_hash = hashing::hash_node(domain, hash, &old_hash);
+                new_hash = hashing::hash_node(domain, hash, &new_hash);
+                while old_node.is_multiple_of(2) && old_node != 0 {
+                    old_node >>= 1;
+                    new_node >>= 1;
+                }
+            } else {
+                new_hash = hashing::hash_node(domain, &new_hash, hash);
+            }
+            old_node >>= 1;
+            new_node >>= 1;
+        }
+
+        new_node == 0 && old_hash == old_root && new_hash == new_root
+    }
+}
+
+/// Here I do the combinations to reach the root of a tree with len leaves. When a level has no sibling to combine
+/// with (promote padding), the hash goes up unchanged. Returns None if the index is out of the tree, or if the proof
+/// has more or less siblings than levels the leaf has to climb
+pub(crate) fn root_from_proof(
+    domain: &DomainSeparation,
+    padding: Padding,
+    len: usize,
+    proof: Vec<String>,
+    leaf: String,
+    index: usize,
+) -> Option<String> {
+    climb(
+        padding,
+        len,
+        index,
+        leaf,
+        proof.into_iter(),
+        |left, right| hashing::hash_node(domain, left, right),
+    )
+}
+
+/// The walk of root_from_proof, for any representation of the hashes
+fn climb<H>(
+    padding: Padding,
+    len: usize,
+    mut index: usize,
+    leaf: H,
+    mut proof: impl ExactSizeIterator<Item = H>,
+    combine: impl Fn(&H, &H) -> H,
+) -> Option<H> {
+    if index >= len {
+        return None;
+    }
+
+    let depth = depth_of(len);
+    let proof_length = (0..depth)
+        .filter(|height| !sibling_is_missing(padding, len, index >> height, *height))
+        .count();
+    if proof.len() != proof_length {
+        return None;
+    }
+
+    let mut hash = leaf;
+    for height in 0..depth {
+        if !sibling_is_missing(padding, len, index, height) {
+            let proof_element = proof.next()?;
+            if index.is_multiple_of(2) {
+                hash = combine(&hash, &proof_element);
+            } else {
+                hash = combine(&proof_element, &hash);
+            }
+        }
+
+        index /= 2;
+    }
+    Some(hash)
+}
+
+/// Only with promote padding a sibling can be missing, in the rest of strategies the padding nodes are part of the proof
+pub(crate) fn sibling_is_missing(padding: Padding, len: usize, index: usize, height: u32) -> bool {
+    let sibling = index ^ 1;
+    // A leftmost leaf past usize::MAX is out of any tree, checked_shl alone would drop the high bits
+    padding == Padding::Promote
+        && (sibling > usize::MAX >> height.min(usize::BITS - 1) || sibling << height >= len)
+}
+
+/// Amount of levels below the root of a tree with len leaves. Even a single leaf has a (padding) sibling
+pub(crate) fn depth_of(len: usize) -> u32 {
+    len.checked_next_power_of_two()
+        .map_or(usize::BITS, |width| width.max(2).trailing_zeros())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{depth_of, FixedProof, MerkleProof};
+    use crate::hashing::DomainSeparation;
+    use crate::merkle_tree::{MerkleTree, Padding};
+
+    #[test]
+    fn test_01_proofs_are_verified_without_the_tree() {
+        for padding in [Padding::DuplicateLast, Padding::Zero, Padding::Promote] {
+            let mut tree = MerkleTree::new()
+                .with_padding(padding)
+                .with_domain_separation(DomainSeparation::rfc6962());
+            for text in ["a", "b", "c", "d", "e"] {
+                tree.add_unhashed(text.to_string());
+            }
+            let root = tree.root().unwrap();
+
+            for index in 0..5 {
+                let proof = tree.prove(index).unwrap();
+                assert_eq!(tree.generate_proof(index), proof.siblings);
+                assert!(proof.verify(&root, padding, &DomainSeparation::rfc6962()));
+                assert!(!proof.verify(&root, padding, &DomainSeparation::default()));
+            }
+            assert_eq!(None, tree.prove(5));
+        }
+    }
+
+    #[test]
+    fn test_02_proofs_of_another_size_or_index_dont_verify() {
+        let tree = MerkleTree::build(vec!["a", "b", "c", "d", "e"], true);
+        let root = tree.root().unwrap();
+        let domain = DomainSeparation::default();
+        let proof = tree.prove(4).unwrap();
+
+        for changed in [
+            MerkleProof {
+                index: 3,
+                ..proof.clone()
+            },
+            MerkleProof {
+                tree_size: 4,
+                ..proof.clone()
+            },
+            MerkleProof {
+                siblings: proof.siblings[1..].to_vec(),
+                ..proof.clone()
+            },
+        ] {
+            assert!(!changed.verify(&root, Padding::DuplicateLast, &domain));
+        }
+        assert_eq!(3, depth_of(5));
+        assert_eq!(1, depth_of(1));
+    }
+
+    #[test]
+    fn test_03_fixed_proofs_match_the_hex_ones() {
+        let decode = |hash: &str| {
+            let mut bytes = [0; 32];
+            hex::decode_to_slice(hash, &mut bytes).unwrap();
+            bytes
+        };
+        let domain = DomainSeparation::rfc6962();
+        let tree = MerkleTree::build(vec!["a", "b", "c", "d", "e"], true)
+            .with_padding(Padding::Promote)
+            .with_domain_separation(domain.clone());
+        let root = decode(&tree.root().unwrap());
+
+        for index in 0..5 {
+            let proof = tree.prove(index).unwrap();
+            let mut siblings = [[0; 32]; 8];
+            for (sibling, hash) in siblings.iter_mut().zip(&proof.siblings) {
+                *sibling = decode(hash);
+            }
+            let fixed = FixedProof {
+                index,
+                tree_size: 5,
+                leaf: decode(&proof.leaf),
+                siblings: &siblings[..proof.siblings.len()],
+            };
+
+            assert!(fixed.verify(&root, Padding::Promote, &domain));
+            assert!(!fixed.verify(&root, Padding::Promote, &DomainSeparation::default()));
+            assert!(!FixedProof {
+                siblings: &siblings[..proof.siblings.len() + 1],
+                ..fixed
+            }
+            .verify(&root, Padding::Promote, &domain));
+        }
+    }
+
+    #[test]
+    fn test_04_proofs_of_huge_trees_dont_overflow() {
+        assert_eq!(usize::BITS, depth_of(usize::MAX));
+        for padding in [Padding::DuplicateLast, Padding::Zero, Padding::Promote] {
+            for (index, tree_size) in [(0, usize::MAX), (usize::MAX - 1, usize::MAX)] {
+                let fixed = FixedProof {
+                    index,
+                    tree_size,
+                    leaf: [0; 32],
+                    siblings: &[[0; 32]; 3],
+                };
+                assert_eq!(None, fixed.root(padding, &DomainSeparation::default()));
+            }
+        }
+    }
+}
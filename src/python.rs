@@ -0,0 +1,250 @@
+use std::fs;
+
+use pyo3::create_exception;
+use pyo3::exceptions::{PyIndexError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+use crate::errors::MerkleTreeErrors;
+use crate::hashing::{self, DomainSeparation};
+use crate::merkle_tree::MerkleTree;
+
+create_exception!(
+    rusty_merkle_tree,
+    MerkleTreeError,
+    PyValueError,
+    "Invalid hashes and domain tags, and the errors of the crate"
+);
+
+impl From<MerkleTreeErrors> for PyErr {
+    fn from(e: MerkleTreeErrors) -> Self {
+        MerkleTreeError::new_err(e.to_string())
+    }
+}
+
+/// The tree for Python, as `rusty_merkle_tree.MerkleTree`. Hashes can be given as 32 bytes or as 64 hex characters,
+/// and are returned as hex strings
+#[pyclass(name = "MerkleTree")]
+pub struct PyMerkleTree {
+    tree: MerkleTree,
+}
+
+#[pymethods]
+impl PyMerkleTree {
+    /// An empty tree. Both tags enable domain separation, see DomainSeparation::new
+    #[new]
+    #[pyo3(signature = (*, leaf_tag = None, node_tag = None))]
+    fn new(leaf_tag: Option<Vec<u8>>, node_tag: Option<Vec<u8>>) -> PyResult<Self> {
+        let domain = match (leaf_tag, node_tag) {
+            (None, None) => DomainSeparation::default(),
+            (Some(leaf_tag), Some(node_tag)) => DomainSeparation::new(leaf_tag, node_tag)?,
+            _ => return Err(MerkleTreeError::new_err("expected both tags or none")),
+        };
+        Ok(PyMerkleTree {
+            tree: MerkleTree::new().with_domain_separation(domain),
+        })
+    }
+
+    /// `MerkleTree.build([b"a", "b"], unhashed=True)` hashes the values first, without it they must be hashes
+    #[staticmethod]
+    #[pyo3(signature = (values, unhashed = false, *, leaf_tag = None, node_tag = None))]
+    fn build(
+        values: Vec<Bound<'_, PyAny>>,
+        unhashed: bool,
+        leaf_tag: Option<Vec<u8>>,
+        node_tag: Option<Vec<u8>>,
+    ) -> PyResult<Self> {
+        let mut tree = PyMerkleTree::new(leaf_tag, node_tag)?;
+        for value in &values {
+            if unhashed {
+                tree.add_unhashed(value)?;
+            } else {
+                tree.add(value)?;
+            }
+        }
+        Ok(tree)
+    }
+
+    fn add(&mut self, hash: &Bound<'_, PyAny>) -> PyResult<()> {
+        self.tree.add(hash_from(hash)?);
+        Ok(())
+    }
+
+    /// The data can be bytes or a string, which is hashed as UTF-8
+    fn add_unhashed(&mut self, data: &Bound<'_, PyAny>) -> PyResult<()> {
+        let hash = match data.cast::<PyBytes>() {
+            Ok(bytes) => hashing::hash_leaf(self.tree.domain_separation(), bytes.as_bytes()),
+            Err(_) => {
+                let text: String = data.extract()?;
+                hashing::hash_leaf(self.tree.domain_separation(), text.as_bytes())
+            }
+        };
+        self.tree.add(hash);
+        Ok(())
+    }
+
+    /// None for an empty tree
+    fn root(&self) -> Option<String> {
+        self.tree.root()
+    }
+
+    /// The siblings of the leaf from the bottom to the top. IndexError if there is no leaf at the index
+    fn proof(&self, index: usize) -> PyResult<Vec<String>> {
+        self.tree
+            .prove(index)
+            .map(|proof| proof.siblings)
+            .ok_or_else(|| PyIndexError::new_err(format!("there is no leaf at {index}")))
+    }
+
+    fn verify(
+        &self,
+        proof: Vec<Bound<'_, PyAny>>,
+        leaf: &Bound<'_, PyAny>,
+        index: usize,
+    ) -> PyResult<bool> {
+        let proof = proof.iter().map(hash_from).collect::<PyResult<_>>()?;
+        Ok(self.tree.verify(proof, hash_from(leaf)?, index))
+    }
+
+    /// Writes the leaves, a hash per line, the same file the HTTP server loads. The tags are not saved
+    fn save(&self, path: &str) -> PyResult<()> {
+        let leaves: String = self
+            .tree
+            .leaves()
+            .into_iter()
+            .map(|leaf| leaf + "\n")
+            .collect();
+        fs::write(path, leaves)?;
+        Ok(())
+    }
+
+    /// Reads a file written by save. The tags must be the ones of the saved tree to get the same root
+    #[staticmethod]
+    #[pyo3(signature = (path, *, leaf_tag = None, node_tag = None))]
+    fn load(path: &str, leaf_tag: Option<Vec<u8>>, node_tag: Option<Vec<u8>>) -> PyResult<Self> {
+        let mut tree = PyMerkleTree::new(leaf_tag, node_tag)?;
+        for leaf in fs::read_to_string(path)?.lines().map(str::trim) {
+            if !leaf.is_empty() {
+                tree.tree.add(checked_hex(leaf)?);
+            }
+        }
+        Ok(tree)
+    }
+
+    fn __len__(&self) -> usize {
+        self.tree.len()
+    }
+}
+
+/// A hash given as 32 bytes or as 64 hex characters, hex encoded
+fn hash_from(value: &Bound<'_, PyAny>) -> PyResult<String> {
+    if let Ok(bytes) = value.cast::<PyBytes>() {
+        let bytes = bytes.as_bytes();
+        if bytes.len() != 32 {
+            return Err(MerkleTreeError::new_err(format!(
+                "a hash has 32 bytes, got {}",
+                bytes.len()
+            )));
+        }
+        return Ok(hex::encode(bytes));
+    }
+    checked_hex(value.extract::<&str>()?)
+}
+
+fn checked_hex(hash: &str) -> PyResult<String> {
+    if hash.len() != 64 || !hash.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(MerkleTreeError::new_err(format!(
+            "a hash has 64 hex characters, got {hash:?}"
+        )));
+    }
+    Ok(hash.to_string())
+}
+
+/// The `rusty_merkle_tree` module, built by bindings/python with maturin
+#[pymodule]
+#[pyo3(name = "rusty_merkle_tree")]
+pub fn python_module(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyMerkleTree>()?;
+    m.add("MerkleTreeError", m.py().get_type::<MerkleTreeError>())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::CString;
+
+    use pyo3::prelude::*;
+    use pyo3::types::{PyDict, PyModule};
+
+    use super::python_module;
+
+    /// Runs the script with the module imported as mt
+    fn run(script: &str) {
+        Python::initialize();
+        Python::attach(|py| {
+            let module = PyModule::new(py, "rusty_merkle_tree").unwrap();
+            python_module(&module).unwrap();
+            let globals = PyDict::new(py);
+            globals.set_item("mt", module).unwrap();
+
+            let script = CString::new(script).unwrap();
+            if let Err(e) = py.run(&script, Some(&globals), None) {
+                panic!("{e}");
+            }
+        });
+    }
+
+    #[test]
+    fn test_01_trees_take_bytes_and_hex_and_prove_their_leaves() {
+        run(r#"
+HASH_A = "ca978112ca1bbdcafac231b39a23dc4da786eff8147c4e72b9807785afee48bb"
+
+tree = mt.MerkleTree.build([b"a", "b"], unhashed=True)
+same = mt.MerkleTree()
+same.add(bytes.fromhex(HASH_A))
+same.add_unhashed("b")
+assert len(tree) == 2
+assert tree.root() == same.root() == "62af5c3cb8da3e4f25061e829ebeea5c7513c54949115b1acc225930a90154da"
+
+proof = tree.proof(0)
+assert tree.verify(proof, HASH_A, 0)
+assert tree.verify([bytes.fromhex(h) for h in proof], bytes.fromhex(HASH_A), 0)
+assert not tree.verify(proof, HASH_A, 1)
+assert mt.MerkleTree().root() is None
+"#);
+    }
+
+    #[test]
+    fn test_02_errors_are_raised_as_python_exceptions() {
+        run(r#"
+def raises(exception, function):
+    try:
+        function()
+    except exception:
+        return True
+    return False
+
+tree = mt.MerkleTree.build(["a"], unhashed=True)
+assert raises(IndexError, lambda: tree.proof(1))
+assert raises(mt.MerkleTreeError, lambda: tree.add("abc"))
+assert raises(mt.MerkleTreeError, lambda: tree.add(b"short"))
+assert raises(ValueError, lambda: mt.MerkleTree(leaf_tag=b"tag", node_tag=b"tag"))
+assert raises(OSError, lambda: mt.MerkleTree.load("/nonexistent/leaves"))
+"#);
+    }
+
+    #[test]
+    fn test_03_saved_trees_are_loaded_with_the_same_root() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("leaves");
+        run(&format!(
+            r#"
+tree = mt.MerkleTree.build(["a", "b", "c"], unhashed=True, leaf_tag=b"\x00", node_tag=b"\x01")
+tree.save({path:?})
+loaded = mt.MerkleTree.load({path:?}, leaf_tag=b"\x00", node_tag=b"\x01")
+assert loaded.root() == tree.root()
+assert mt.MerkleTree.load({path:?}).root() != tree.root()
+"#
+        ));
+    }
+}
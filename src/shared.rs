@@ -0,0 +1,214 @@
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError, RwLock};
+
+use crate::merkle_tree::MerkleTree;
+use crate::snapshot::MerkleSnapshot;
+
+/// A MerkleTree that can be shared between threads. The reads are served from a snapshot of the last version, which
+/// is published again after every write, so any amount of readers can generate and verify proofs without ever
+/// waiting for a writer, while the writes take turns. A reader never sees a half done add, and everything read
+/// inside a single `read` call comes from the same version of the tree
+#[derive(Clone)]
+pub struct SharedMerkleTree {
+    tree: Arc<Mutex<MerkleTree>>,
+    /// Only locked to clone or replace the Arc of the snapshot, never while the tree is changing
+    published: Arc<RwLock<MerkleSnapshot>>,
+}
+
+impl Default for SharedMerkleTree {
+    fn default() -> Self {
+        Self::new(MerkleTree::new())
+    }
+}
+
+impl SharedMerkleTree {
+    pub fn new(mut tree: MerkleTree) -> Self {
+        let snapshot = tree.snapshot();
+        SharedMerkleTree {
+            tree: Arc::new(Mutex::new(tree)),
+            published: Arc::new(RwLock::new(snapshot)),
+        }
+    }
+
+    /// Runs a function with the last published version, so all it reads is consistent
+    pub fn read<T>(&self, f: impl FnOnce(&MerkleSnapshot) -> T) -> T {
+        f(&self.snapshot())
+    }
+
+    /// Runs a function with the tree locked for writing, to make many changes that readers will see at once. They
+    /// keep reading the previous version until the function returns
+    pub fn write<T>(&self, f: impl FnOnce(&mut MerkleTree) -> T) -> T {
+        let mut tree = self.write_lock();
+        let result = f(&mut tree);
+
+        // Only the nodes that changed are frozen, the rest are shared with the previous snapshot
+        let snapshot = tree.snapshot();
+        *self
+            .published
+            .write()
+            .unwrap_or_else(PoisonError::into_inner) = snapshot;
+        result
+    }
+
+    pub fn add(&self, hashed_string: String) {
+        self.write(|tree| tree.add(hashed_string));
+    }
+
+    pub fn add_unhashed(&self, unhashed_text: String) {
+        self.write(|tree| tree.add_unhashed(unhashed_text));
+    }
+
+    /// The proof of a leaf together with the root it leads to, both taken from the same version of the tree.
+    /// Returns None if there is no leaf at the index
+    pub fn generate_proof(&self, index: usize) -> Option<(Vec<String>, String)> {
+        self.read(|tree| {
+            if index >= tree.len() {
+                return None;
+            }
+            let root = tree.root()?.to_string();
+            Some((tree.generate_proof(index), root))
+        })
+    }
+
+    /// The last published version, to keep serving its proofs while the tree changes
+    pub fn snapshot(&self) -> MerkleSnapshot {
+        self.published
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    pub fn verify(&self, proof: Vec<String>, leaf: String, index: usize) -> bool {
+        self.read(|tree| tree.verify(proof, leaf, index))
+    }
+
+    pub fn root(&self) -> Option<String> {
+        self.read(|tree| tree.root().map(str::to_string))
+    }
+
+    pub fn len(&self) -> usize {
+        self.read(|tree| tree.len())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.read(|tree| tree.is_empty())
+    }
+
+    /// If a thread panicked while writing, the tree keeps being used instead of panicking in every writer. The
+    /// readers keep the version published before it
+    fn write_lock(&self) -> MutexGuard<'_, MerkleTree> {
+        self.tree.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+    use std::thread;
+
+    use super::SharedMerkleTree;
+    use crate::merkle_tree::MerkleTree;
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn test_01_trees_can_be_sent_and_shared_between_threads() {
+        assert_send_sync::<MerkleTree>();
+        assert_send_sync::<SharedMerkleTree>();
+    }
+
+    #[test]
+    fn test_02_readers_see_consistent_versions_while_a_writer_appends() {
+        let shared = SharedMerkleTree::new(MerkleTree::build(vec!["0"], true));
+
+        let writer = {
+            let shared = shared.clone();
+            thread::spawn(move || {
+                for i in 1..200 {
+                    shared.add_unhashed(i.to_string());
+                }
+            })
+        };
+
+        let readers: Vec<_> = (0..4)
+            .map(|reader| {
+                let shared = shared.clone();
+                thread::spawn(move || {
+                    for i in 0..200 {
+                        let checked = shared.read(|tree| {
+                            let index = (reader * 31 + i) % tree.len();
+                            let proof = tree.generate_proof(index);
+                            let leaf = tree.leaf(index).unwrap().to_string();
+                            tree.verify(proof, leaf, index)
+                        });
+                        assert!(checked);
+                    }
+                })
+            })
+            .collect();
+
+        writer.join().unwrap();
+        for reader in readers {
+            reader.join().unwrap();
+        }
+
+        assert_eq!(200, shared.len());
+        let mut expected = MerkleTree::new();
+        for i in 0..200 {
+            expected.add_unhashed(i.to_string());
+        }
+        assert_eq!(expected.root(), shared.root());
+    }
+
+    #[test]
+    fn test_03_proofs_come_with_the_root_of_their_version() {
+        let shared = SharedMerkleTree::default();
+        assert!(shared.is_empty());
+        assert_eq!(None, shared.generate_proof(0));
+
+        shared.write(|tree| {
+            for text in ["a", "b", "c"] {
+                tree.add_unhashed(text.to_string());
+            }
+        });
+        let (proof, root) = shared.generate_proof(2).unwrap();
+        shared.add_unhashed("d".to_string());
+
+        // The tree changed, so the old proof doesn't lead to the current root anymore
+        assert_ne!(Some(root), shared.root());
+        let leaf = shared.read(|tree| tree.leaf(2).unwrap().to_string());
+        assert!(!shared.verify(proof, leaf.clone(), 2));
+
+        let (proof, _) = shared.generate_proof(2).unwrap();
+        assert!(shared.verify(proof, leaf, 2));
+    }
+
+    #[test]
+    fn test_04_readers_dont_wait_for_a_writer() {
+        let shared = SharedMerkleTree::new(MerkleTree::build(vec!["a", "b", "c"], true));
+        let (started, wait_started) = mpsc::channel();
+        let (release, wait_release) = mpsc::channel::<()>();
+
+        let writer = {
+            let shared = shared.clone();
+            thread::spawn(move || {
+                shared.write(|tree| {
+                    tree.add_unhashed("d".to_string());
+                    started.send(()).unwrap();
+                    wait_release.recv().unwrap();
+                })
+            })
+        };
+
+        // The writer is in the middle of its write, and the readers get the version before it
+        wait_started.recv().unwrap();
+        assert_eq!(3, shared.len());
+        let (proof, root) = shared.generate_proof(2).unwrap();
+        let leaf = shared.read(|tree| tree.leaf(2).unwrap().to_string());
+        assert!(shared.verify(proof, leaf, 2));
+
+        release.send(()).unwrap();
+        writer.join().unwrap();
+        assert_eq!(4, shared.len());
+        assert_ne!(Some(root), shared.root());
+    }
+}
@@ -0,0 +1,287 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+
+use crate::errors::SigningErrors;
+use crate::hashing::{DomainSeparation, HashFunction, NodeEncoding};
+use crate::merkle_tree::{MerkleTree, Padding};
+
+/// Goes first in the signed data, so these signatures can't be taken for ones of anything else signed with the same
+/// key. The version changes if the layout of the signed data does
+const SIGNATURE_CONTEXT: &[u8] = b"rusty-merkle-tree signed tree head v1";
+
+/// A root signed by the owner of the tree, so clients can check that a root they fetched is one the owner published.
+/// The size and the time are signed together with it, and so are the padding and the domain separation of the tree,
+/// so the root can't be passed off as one of a tree hashed differently
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignedTreeHead {
+    pub tree_size: u64,
+    /// Milliseconds since the UNIX epoch
+    pub timestamp: u64,
+    pub root: String,
+    pub signature: [u8; 64],
+}
+
+impl SignedTreeHead {
+    /// Signs the current root of the tree with the current time
+    pub fn sign(tree: &MerkleTree, key: &SigningKey) -> Result<Self, SigningErrors> {
+        let root = tree.root().ok_or(SigningErrors::EmptyTreeError)?;
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_millis() as u64)
+            .unwrap_or_default();
+
+        Ok(SignedTreeHead::sign_at(
+            tree.len() as u64,
+            timestamp,
+            root,
+            tree.padding(),
+            tree.domain_separation(),
+            key,
+        ))
+    }
+
+    pub fn sign_at(
+        tree_size: u64,
+        timestamp: u64,
+        root: String,
+        padding: Padding,
+        domain: &DomainSeparation,
+        key: &SigningKey,
+    ) -> Self {
+        let signature = key.sign(&signed_data(tree_size, timestamp, &root, padding, domain));
+        SignedTreeHead {
+            tree_size,
+            timestamp,
+            root,
+            signature: signature.to_bytes(),
+        }
+    }
+
+    /// Checks the signature for a tree with the given padding and domain separation, the ones the client hashes with.
+    /// Heads signed for a tree with other ones are not verified
+    pub fn verify(&self, key: &VerifyingKey, padding: Padding, domain: &DomainSeparation) -> bool {
+        let data = signed_data(self.tree_size, self.timestamp, &self.root, padding, domain);
+        key.verify_strict(&data, &Signature::from_bytes(&self.signature))
+            .is_ok()
+    }
+
+    /// A single word, to pass it around as a command argument: size:timestamp:root:signature
+    pub fn serialize(&self) -> String {
+        format!(
+            "{}:{}:{}:{}",
+            self.tree_size,
+            self.timestamp,
+            self.root,
+            hex::encode(self.signature)
+        )
+    }
+
+    pub fn deserialize(serialized: &str) -> Result<Self, SigningErrors> {
+        let malformed = |reason: &str| SigningErrors::MalformedTreeHeadError(reason.to_string());
+
+        let parts: Vec<&str> = serialized.trim().split(':').collect();
+        let [tree_size, timestamp, root, signature] = parts[..] else {
+            return Err(malformed("expected size:timestamp:root:signature"));
+        };
+
+        let mut signature_bytes = [0; 64];
+        hex::decode_to_slice(signature, &mut signature_bytes)
+            .map_err(|_| malformed("the signature is not 64 hex encoded bytes"))?;
+
+        Ok(SignedTreeHead {
+            tree_size: tree_size
+                .parse()
+                .map_err(|_| malformed("the size is not a number"))?,
+            timestamp: timestamp
+                .parse()
+                .map_err(|_| malformed("the timestamp is not a number"))?,
+            root: root.to_string(),
+            signature: signature_bytes,
+        })
+    }
+}
+
+/// The context, the parameters the tree is hashed with, the timestamp and the size as big endian numbers and the
+/// root. The tags are preceded by their length and the root goes last, so nothing else needs to be delimited
+fn signed_data(
+    tree_size: u64,
+    timestamp: u64,
+    root: &str,
+    padding: Padding,
+    domain: &DomainSeparation,
+) -> Vec<u8> {
+    let mut data = Vec::new();
+    data.extend_from_slice(SIGNATURE_CONTEXT);
+
+    match domain.hash_function() {
+        HashFunction::Sha256 => data.push(0),
+        #[cfg(feature = "blake3")]
+        HashFunction::Blake3 => data.push(1),
+        #[cfg(feature = "blake3")]
+        HashFunction::Blake3Keyed { leaf_key, node_key } => {
+            data.push(2);
+            data.extend_from_slice(&leaf_key);
+            data.extend_from_slice(&node_key);
+        }
+    }
+    data.push(match domain.node_encoding() {
+        NodeEncoding::Hex => 0,
+        NodeEncoding::Raw => 1,
+    });
+    data.push(match padding {
+        Padding::DuplicateLast => 0,
+        Padding::Zero => 1,
+        Padding::Promote => 2,
+    });
+    for tag in [domain.leaf_tag(), domain.node_tag()] {
+        data.extend_from_slice(&(tag.len() as u64).to_be_bytes());
+        data.extend_from_slice(tag);
+    }
+
+    data.extend_from_slice(&timestamp.to_be_bytes());
+    data.extend_from_slice(&tree_size.to_be_bytes());
+    data.extend_from_slice(root.as_bytes());
+    data
+}
+
+/// A new random private key
+pub fn generate_key() -> Result<SigningKey, SigningErrors> {
+    let mut secret = [0; 32];
+    getrandom::getrandom(&mut secret)
+        .map_err(|e| SigningErrors::InvalidKeyError(format!("no randomness available: {e}")))?;
+    Ok(SigningKey::from_bytes(&secret))
+}
+
+/// Keys are saved as 32 hex encoded bytes
+pub fn signing_key_from_hex(key: &str) -> Result<SigningKey, SigningErrors> {
+    Ok(SigningKey::from_bytes(&key_bytes_from_hex(key)?))
+}
+
+pub fn verifying_key_from_hex(key: &str) -> Result<VerifyingKey, SigningErrors> {
+    VerifyingKey::from_bytes(&key_bytes_from_hex(key)?)
+        .map_err(|_| SigningErrors::InvalidKeyError("not a valid public key".to_string()))
+}
+
+fn key_bytes_from_hex(key: &str) -> Result<[u8; 32], SigningErrors> {
+    let mut bytes = [0; 32];
+    hex::decode_to_slice(key.trim(), &mut bytes)
+        .map_err(|_| SigningErrors::InvalidKeyError("expected 32 hex encoded bytes".to_string()))?;
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use ed25519_dalek::SigningKey;
+
+    use super::{signing_key_from_hex, verifying_key_from_hex, SignedTreeHead};
+    use crate::errors::SigningErrors;
+    use crate::hashing::{DomainSeparation, NodeEncoding};
+    use crate::merkle_tree::{MerkleTree, Padding};
+
+    // Secret and public key of the first test vector of RFC 8032
+    const SECRET: &str = "9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60";
+    const PUBLIC: &str = "d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a";
+
+    #[test]
+    fn test_01_signed_tree_heads_are_verified_with_the_public_key() {
+        let key = signing_key_from_hex(SECRET).unwrap();
+        let tree = MerkleTree::build(vec!["a", "b", "c"], true);
+
+        let head = SignedTreeHead::sign(&tree, &key).unwrap();
+        assert_eq!(3, head.tree_size);
+        assert_eq!(tree.root(), Some(head.root.clone()));
+        assert_eq!(PUBLIC, hex::encode(key.verifying_key().to_bytes()));
+        let public = verifying_key_from_hex(PUBLIC).unwrap();
+        assert!(head.verify(&public, tree.padding(), tree.domain_separation()));
+
+        let other_key = SigningKey::from_bytes(&[7; 32]).verifying_key();
+        assert!(!head.verify(&other_key, tree.padding(), tree.domain_separation()));
+    }
+
+    #[test]
+    fn test_02_changing_any_field_breaks_the_signature() {
+        let key = signing_key_from_hex(SECRET).unwrap();
+        let public = key.verifying_key();
+        let domain = DomainSeparation::default();
+        let head = SignedTreeHead::sign_at(
+            3,
+            1_700_000_000_000,
+            "ab".repeat(32),
+            Padding::DuplicateLast,
+            &domain,
+            &key,
+        );
+
+        for changed in [
+            SignedTreeHead {
+                tree_size: 4,
+                ..head.clone()
+            },
+            SignedTreeHead {
+                timestamp: 1_700_000_000_001,
+                ..head.clone()
+            },
+            SignedTreeHead {
+                root: "ac".repeat(32),
+                ..head.clone()
+            },
+        ] {
+            assert!(!changed.verify(&public, Padding::DuplicateLast, &domain));
+        }
+        assert!(head.verify(&public, Padding::DuplicateLast, &domain));
+    }
+
+    #[test]
+    fn test_03_serialized_tree_heads_are_read_back() {
+        let key = signing_key_from_hex(SECRET).unwrap();
+        let head = SignedTreeHead::sign_at(
+            3,
+            1_700_000_000_000,
+            "ab".repeat(32),
+            Padding::DuplicateLast,
+            &DomainSeparation::default(),
+            &key,
+        );
+
+        let serialized = head.serialize();
+        assert!(serialized.starts_with(&format!("3:1700000000000:{}:", "ab".repeat(32))));
+        assert_eq!(head, SignedTreeHead::deserialize(&serialized).unwrap());
+
+        assert!(matches!(
+            SignedTreeHead::deserialize("3:1700000000000:abab"),
+            Err(SigningErrors::MalformedTreeHeadError(_))
+        ));
+        assert!(matches!(
+            SignedTreeHead::sign(&MerkleTree::new(), &key),
+            Err(SigningErrors::EmptyTreeError)
+        ));
+    }
+
+    #[test]
+    fn test_04_heads_only_verify_with_the_parameters_they_were_signed_for() {
+        let key = signing_key_from_hex(SECRET).unwrap();
+        let public = key.verifying_key();
+        let tree = MerkleTree::build(vec!["a", "b", "c"], true)
+            .with_domain_separation(DomainSeparation::rfc6962());
+        let head = SignedTreeHead::sign(&tree, &key).unwrap();
+        assert!(head.verify(&public, tree.padding(), tree.domain_separation()));
+
+        let rfc6962 = DomainSeparation::rfc6962();
+        for (padding, domain) in [
+            (Padding::Promote, rfc6962.clone()),
+            (Padding::Zero, rfc6962.clone()),
+            (Padding::DuplicateLast, DomainSeparation::default()),
+            (
+                Padding::DuplicateLast,
+                rfc6962.clone().with_node_encoding(NodeEncoding::Raw),
+            ),
+            (
+                Padding::DuplicateLast,
+                DomainSeparation::new(vec![0, 0], vec![1]).unwrap(),
+            ),
+        ] {
+            assert!(!head.verify(&public, padding, &domain));
+        }
+    }
+}
@@ -0,0 +1,384 @@
+use alloc::string::{String, ToString};
+use alloc::sync::{Arc, Weak};
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::hashing::DomainSeparation;
+use crate::merkle_tree::{MerkleTree, Padding};
+use crate::proof::{self, MerkleProof};
+
+/// A node of a frozen tree. The children are behind an Arc so the subtrees that didn't change from one snapshot to
+/// the next one are the same allocation in both
+#[derive(Debug)]
+pub(crate) enum Node {
+    Leaf(String),
+    /// The right child is missing when it would be all padding with the promote strategy
+    Inner {
+        hash: String,
+        left: Arc<Node>,
+        right: Option<Arc<Node>>,
+    },
+}
+
+impl Node {
+    fn hash(&self) -> &str {
+        match self {
+            Node::Leaf(hash) | Node::Inner { hash, .. } => hash,
+        }
+    }
+}
+
+/// An immutable version of a MerkleTree. It can generate and verify proofs of the leaves it had when it was taken,
+/// while the tree keeps receiving new ones. Cloning it is cheap, it only clones the Arc of the root.
+/// Its nodes are a copy of the ones of the tree, shared only with the other snapshots of the same tree: the first one
+/// takes as much memory again as the tree, and the next ones only add the nodes that changed while an earlier one is
+/// still alive
+#[derive(Debug, Clone)]
+pub struct MerkleSnapshot {
+    root: Option<Arc<Node>>,
+    len: usize,
+    depth: u32,
+    padding: Padding,
+    domain: DomainSeparation,
+}
+
+impl MerkleSnapshot {
+    pub fn root(&self) -> Option<&str> {
+        self.root.as_deref().map(Node::hash)
+    }
+
+    /// Amount of leaves the tree had when the snapshot was taken, without the padding
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn depth(&self) -> u32 {
+        self.depth
+    }
+
+    pub fn padding(&self) -> Padding {
+        self.padding
+    }
+
+    pub fn domain_separation(&self) -> &DomainSeparation {
+        &self.domain
+    }
+
+    pub fn leaf(&self, index: usize) -> Option<&str> {
+        if index >= self.len {
+            return None;
+        }
+        self.subtree(0, index).map(|node| node.hash())
+    }
+
+    /// Same proof the tree gave for the leaf at the moment of the snapshot, from the bottom to the top. It's empty if
+    /// there is no leaf at the index
+    pub fn generate_proof(&self, index: usize) -> Vec<String> {
+        if index >= self.len {
+            return Vec::new();
+        }
+
+        let mut proof = Vec::new();
+        let mut node = self.root.as_deref();
+        for height in (0..self.depth).rev() {
+            let Some(Node::Inner { left, right, .. }) = node else {
+                break;
+            };
+            let (next, sibling) = if (index >> height).is_multiple_of(2) {
+                (Some(left), right.as_ref())
+            } else {
+                (right.as_ref(), Some(left))
+            };
+            if let Some(sibling) = sibling {
+                proof.push(sibling.hash().to_string());
+            }
+            node = next.map(|next| next.as_ref());
+        }
+
+        proof.reverse();
+        proof
+    }
+
+    /// The proof of a leaf together with what is needed to verify it without the snapshot. None if there is no leaf
+    /// at the index
+    pub fn prove(&self, index: usize) -> Option<MerkleProof> {
+        Some(MerkleProof {
+            index,
+            tree_size: self.len,
+            leaf: self.leaf(index)?.to_string(),
+            siblings: self.generate_proof(index),
+        })
+    }
+
+    pub fn verify(&self, proof: Vec<String>, leaf: String, index: usize) -> bool {
+        proof::root_from_proof(&self.domain, self.padding, self.len, proof, leaf, index)
+            .is_some_and(|root| Some(root.as_str()) == self.root())
+    }
+
+    /// A handle that doesn't keep the nodes alive, for the tree to share them with its next snapshot only while
+    /// someone still holds this one
+    pub(crate) fn downgrade(&self) -> WeakSnapshot {
+        WeakSnapshot {
+            root: self.root.as_ref().map(Arc::downgrade),
+            len: self.len,
+            depth: self.depth,
+            padding: self.padding,
+            domain: self.domain.clone(),
+        }
+    }
+
+    /// The node with the given height over the leaves (0 for the leaves themselves) and index within its level
+    fn subtree(&self, height: u32, index: usize) -> Option<&Arc<Node>> {
+        if height > self.depth {
+            return None;
+        }
+
+        let mut node = self.root.as_ref();
+        for level_height in (height..self.depth).rev() {
+            node = match node.map(|node| node.as_ref()) {
+                Some(Node::Inner { left, right, .. }) => {
+                    if (index >> (level_height - height)).is_multiple_of(2) {
+                        Some(left)
+                    } else {
+                        right.as_ref()
+                    }
+                }
+                _ => None,
+            };
+        }
+        node
+    }
+}
+
+/// A snapshot whose nodes may have been dropped already
+#[derive(Debug)]
+pub(crate) struct WeakSnapshot {
+    root: Option<Weak<Node>>,
+    len: usize,
+    depth: u32,
+    padding: Padding,
+    domain: DomainSeparation,
+}
+
+impl WeakSnapshot {
+    /// The snapshot back, None if every copy of it was dropped
+    pub(crate) fn upgrade(&self) -> Option<MerkleSnapshot> {
+        let root = match &self.root {
+            Some(root) => Some(root.upgrade()?),
+            None => None,
+        };
+        Some(MerkleSnapshot {
+            root,
+            len: self.len,
+            depth: self.depth,
+            padding: self.padding,
+            domain: self.domain.clone(),
+        })
+    }
+}
+
+/// Takes the hashes of the tree into Arc nodes. The subtrees whose leaves were all already in the previous snapshot
+/// are taken from it instead of being created again, so only the new leaves and the path above them are allocated.
+/// The previous snapshot must come from the same tree, with the same leaves, padding and domain separation
+pub(crate) fn freeze(tree: &MerkleTree, previous: Option<&MerkleSnapshot>) -> MerkleSnapshot {
+    let mut freezer = Freezer {
+        tree,
+        previous,
+        padding_nodes: vec![None; tree.depth() as usize + 1],
+    };
+
+    MerkleSnapshot {
+        root: if tree.is_empty() {
+            None
+        } else {
+            freezer.node(tree.depth(), 0)
+        },
+        len: tree.len(),
+        depth: tree.depth(),
+        padding: tree.padding(),
+        domain: tree.domain_separation().clone(),
+    }
+}
+
+struct Freezer<'a> {
+    tree: &'a MerkleTree,
+    previous: Option<&'a MerkleSnapshot>,
+    /// Subtrees with only padding leaves are all equal, so a single one per height is shared by all of them
+    padding_nodes: Vec<Option<Arc<Node>>>,
+}
+
+impl Freezer<'_> {
+    fn node(&mut self, height: u32, index: usize) -> Option<Arc<Node>> {
+        let only_padding = index << height >= self.tree.len();
+        if only_padding {
+            if self.tree.padding() == Padding::Promote {
+                return None;
+            }
+            if let Some(node) = &self.padding_nodes[height as usize] {
+                return Some(Arc::clone(node));
+            }
+        } else if let Some(previous) = self.previous {
+            if (index + 1) << height <= previous.len() {
+                if let Some(node) = previous.subtree(height, index) {
+                    return Some(Arc::clone(node));
+                }
+            }
+        }
+
+        let hash = self.tree.node(self.tree.depth() - height, index)?;
+        let node = if height == 0 {
+            Arc::new(Node::Leaf(hash))
+        } else {
+            Arc::new(Node::Inner {
+                hash,
+                left: self.node(height - 1, 2 * index)?,
+                right: self.node(height - 1, 2 * index + 1),
+            })
+        };
+
+        if only_padding {
+            self.padding_nodes[height as usize] = Some(Arc::clone(&node));
+        }
+        Some(node)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::MerkleSnapshot;
+    use crate::hashing::DomainSeparation;
+    use crate::merkle_tree::{MerkleTree, Padding};
+
+    const PADDINGS: [Padding; 3] = [Padding::DuplicateLast, Padding::Zero, Padding::Promote];
+
+    fn assert_matches_tree(snapshot: &MerkleSnapshot, tree: &MerkleTree) {
+        assert_eq!(tree.root().as_deref(), snapshot.root());
+        assert_eq!(tree.len(), snapshot.len());
+        assert_eq!(tree.depth(), snapshot.depth());
+        for index in 0..tree.len() {
+            let proof = snapshot.generate_proof(index);
+            assert_eq!(tree.generate_proof(index), proof);
+            assert_eq!(tree.leaf(index).as_deref(), snapshot.leaf(index));
+            assert!(snapshot.verify(proof, tree.leaf(index).unwrap(), index));
+        }
+    }
+
+    #[test]
+    fn test_01_snapshots_match_the_tree_they_were_taken_from() {
+        for padding in PADDINGS {
+            let mut tree = MerkleTree::new()
+                .with_padding(padding)
+                .with_domain_separation(DomainSeparation::rfc6962());
+            assert_eq!(None, tree.snapshot().root());
+
+            for i in 0..20 {
+                tree.add_unhashed(i.to_string());
+                assert_matches_tree(&tree.snapshot(), &tree);
+            }
+        }
+    }
+
+    #[test]
+    fn test_02_old_snapshots_keep_serving_their_version() {
+        for padding in PADDINGS {
+            let mut tree = MerkleTree::build(vec!["a", "b", "c"], true).with_padding(padding);
+            let old = tree.snapshot();
+            let old_root = tree.root().unwrap();
+
+            for text in ["d", "e", "f", "g", "h", "i"] {
+                tree.add_unhashed(text.to_string());
+            }
+            let new = tree.snapshot();
+
+            assert_eq!(Some(old_root.as_str()), old.root());
+            assert_ne!(old.root(), new.root());
+            for index in 0..3 {
+                let proof = old.generate_proof(index);
+                let leaf = old.leaf(index).unwrap().to_string();
+                assert!(old.verify(proof.clone(), leaf.clone(), index));
+                assert!(!new.verify(proof, leaf, index));
+            }
+            assert!(old.generate_proof(3).is_empty());
+            assert_matches_tree(&new, &tree);
+        }
+    }
+
+    #[test]
+    fn test_03_unchanged_subtrees_are_shared_between_snapshots() {
+        let texts: Vec<String> = (0..8).map(|i| i.to_string()).collect();
+        let mut tree = MerkleTree::build(texts.iter().map(|t| t.as_str()).collect(), true);
+        let old = tree.snapshot();
+
+        tree.add_unhashed("8".to_string());
+        let new = tree.snapshot();
+
+        // The 8 old leaves are the whole left half of the new tree, so it is the old root itself
+        assert!(Arc::ptr_eq(
+            old.subtree(3, 0).unwrap(),
+            new.subtree(3, 0).unwrap()
+        ));
+        assert!(!Arc::ptr_eq(
+            old.subtree(0, 7).unwrap(),
+            new.subtree(0, 8).unwrap()
+        ));
+
+        tree.add_unhashed("9".to_string());
+        let newest = tree.snapshot();
+        assert!(Arc::ptr_eq(
+            new.subtree(3, 0).unwrap(),
+            newest.subtree(3, 0).unwrap()
+        ));
+        assert!(Arc::ptr_eq(
+            new.subtree(0, 8).unwrap(),
+            newest.subtree(0, 8).unwrap()
+        ));
+        assert_matches_tree(&newest, &tree);
+    }
+
+    #[test]
+    fn test_04_rebuilt_trees_dont_reuse_old_snapshots() {
+        let mut tree = MerkleTree::build(vec!["a", "b", "c", "d"], true);
+        let old = tree.snapshot();
+
+        tree.clear();
+        for text in ["x", "y", "z", "w", "v"] {
+            tree.add_unhashed(text.to_string());
+        }
+        assert_matches_tree(&tree.snapshot(), &tree);
+
+        let mut tree = tree.with_domain_separation(DomainSeparation::rfc6962());
+        assert_matches_tree(&tree.snapshot(), &tree);
+        assert_ne!(old.root(), tree.snapshot().root());
+    }
+
+    #[test]
+    fn test_05_the_tree_doesnt_keep_its_snapshots_alive() {
+        let texts: Vec<String> = (0..8).map(|i| i.to_string()).collect();
+        let mut tree = MerkleTree::build(texts.iter().map(|t| t.as_str()).collect(), true);
+        let old = tree.snapshot();
+        assert_eq!(1, Arc::strong_count(old.root.as_ref().unwrap()));
+
+        let weak = Arc::downgrade(old.subtree(3, 0).unwrap());
+        drop(old);
+        assert!(weak.upgrade().is_none());
+
+        // Without a live snapshot to share with, the next one is copied again and still matches the tree
+        tree.add_unhashed("8".to_string());
+        let new = tree.snapshot();
+        assert_matches_tree(&new, &tree);
+
+        tree.add_unhashed("9".to_string());
+        let newest = tree.snapshot();
+        assert!(Arc::ptr_eq(
+            new.subtree(3, 0).unwrap(),
+            newest.subtree(3, 0).unwrap()
+        ));
+        assert_matches_tree(&newest, &tree);
+    }
+}
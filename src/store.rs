@@ -0,0 +1,405 @@
+use alloc::borrow::ToOwned;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::fs::{File, OpenOptions};
+#[cfg(feature = "std")]
+use std::io;
+#[cfg(feature = "std")]
+use std::path::Path;
+
+#[cfg(feature = "std")]
+use memmap2::MmapMut;
+
+/// Size of a hash in the file store
+#[cfg(feature = "std")]
+const RECORD_SIZE: usize = 32;
+
+/// The file store starts with this magic and the amount of leaves of the tree, before the records
+#[cfg(feature = "std")]
+const FILE_MAGIC: &[u8; 8] = b"MTNODES1";
+#[cfg(feature = "std")]
+const HEADER_SIZE: usize = 16;
+
+/// Where a tree keeps its nodes, in level order: the root at position 0 and the children of the node at position i
+/// at 2i + 1 and 2i + 2. The hashes go in and out hex encoded, however each store decides how to keep them
+pub trait NodeStore: Send + Sync {
+    /// Amount of nodes, counting the padding ones
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Panics if the position is out of the store, like indexing a vector
+    fn get(&self, pos: usize) -> String;
+
+    fn set(&mut self, pos: usize, hash: &str);
+
+    /// Changes the amount of nodes. If it grows, the new ones take the given hash
+    fn resize(&mut self, len: usize, hash: &str);
+
+    /// If the store can keep the hash. Setting one it can't keep panics, so the tree checks its leaves with it
+    fn holds(&self, _hash: &str) -> bool {
+        true
+    }
+
+    fn clear(&mut self) {
+        self.resize(0, "");
+    }
+
+    /// Amount of leaves of the tree, saved with the nodes by the stores that can be opened again. None if the store
+    /// doesn't keep it
+    fn leaves_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn set_leaves_len(&mut self, _len: usize) {}
+
+    #[cfg(feature = "std")]
+    /// Makes sure that the nodes written so far are saved. Nothing to do for the ones that only live in memory
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// The nodes in a vector of hex strings, as the tree always had them. Any string can be stored, even if it's not a
+/// hash, and the missing nodes of the promote padding are empty strings
+#[derive(Debug, Clone, Default)]
+pub struct MemoryStore {
+    nodes: Vec<String>,
+}
+
+impl NodeStore for MemoryStore {
+    fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    fn get(&self, pos: usize) -> String {
+        self.nodes[pos].clone()
+    }
+
+    fn set(&mut self, pos: usize, hash: &str) {
+        hash.clone_into(&mut self.nodes[pos]);
+    }
+
+    fn resize(&mut self, len: usize, hash: &str) {
+        self.nodes.resize(len, hash.to_string());
+    }
+}
+
+#[cfg(feature = "std")]
+/// The nodes in a memory mapped file, as raw 32 byte records one after the other in level order. Only the pages in
+/// use are kept in memory by the OS, so the tree can be much bigger than the RAM. It holds 32 byte hashes only: the
+/// tree refuses leaves that are not 64 hex characters, and the empty nodes are saved as zeros. A header before the
+/// records keeps the amount of leaves, so the file can be opened again with the same tree
+pub struct FileStore {
+    file: File,
+    /// Only None while the file is being resized
+    map: Option<MmapMut>,
+    len: usize,
+}
+
+#[cfg(feature = "std")]
+impl FileStore {
+    /// Creates the file, or empties it if it already exists
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        file.set_len(HEADER_SIZE as u64)?;
+
+        let mut store = FileStore {
+            file,
+            map: None,
+            len: 0,
+        };
+        store.map()?;
+        store.header_mut()[..FILE_MAGIC.len()].copy_from_slice(FILE_MAGIC);
+        Ok(store)
+    }
+
+    /// Opens a file written by another FileStore, keeping its nodes. See MerkleTree::with_existing_store to get the
+    /// tree back
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+        let file_len = file.metadata()?.len() as usize;
+        let invalid = |reason: &str| io::Error::new(io::ErrorKind::InvalidData, reason.to_string());
+        if file_len < HEADER_SIZE || !(file_len - HEADER_SIZE).is_multiple_of(RECORD_SIZE) {
+            return Err(invalid("the file doesn't have the size of a node file"));
+        }
+
+        let mut store = FileStore {
+            file,
+            map: None,
+            len: (file_len - HEADER_SIZE) / RECORD_SIZE,
+        };
+        store.map()?;
+        if &store.header()[..FILE_MAGIC.len()] != FILE_MAGIC {
+            return Err(invalid("the file is not a node file"));
+        }
+        Ok(store)
+    }
+
+    fn map(&mut self) -> io::Result<()> {
+        // SAFETY: the file is only accessed through this store, and the map is dropped before changing its size
+        self.map = Some(unsafe { MmapMut::map_mut(&self.file) }?);
+        Ok(())
+    }
+
+    fn header(&self) -> &[u8] {
+        let map = self.map.as_ref().expect("the store is mapped");
+        &map[..HEADER_SIZE]
+    }
+
+    fn header_mut(&mut self) -> &mut [u8] {
+        let map = self.map.as_mut().expect("the store is mapped");
+        &mut map[..HEADER_SIZE]
+    }
+
+    fn record(&self, pos: usize) -> &[u8] {
+        assert!(pos < self.len, "node {pos} is out of the store");
+        let map = self.map.as_ref().expect("the store is mapped");
+        let start = HEADER_SIZE + pos * RECORD_SIZE;
+        &map[start..start + RECORD_SIZE]
+    }
+
+    fn record_mut(&mut self, pos: usize) -> &mut [u8] {
+        assert!(pos < self.len, "node {pos} is out of the store");
+        let map = self.map.as_mut().expect("the store is mapped");
+        let start = HEADER_SIZE + pos * RECORD_SIZE;
+        &mut map[start..start + RECORD_SIZE]
+    }
+
+    fn decode(hash: &str) -> [u8; RECORD_SIZE] {
+        let mut record = [0; RECORD_SIZE];
+        if !hash.is_empty() {
+            hex::decode_to_slice(hash, &mut record).unwrap_or_else(|_| {
+                panic!("the file store only holds 32 byte hashes, got {hash:?}")
+            });
+        }
+        record
+    }
+}
+
+#[cfg(feature = "std")]
+impl NodeStore for FileStore {
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn get(&self, pos: usize) -> String {
+        hex::encode(self.record(pos))
+    }
+
+    fn set(&mut self, pos: usize, hash: &str) {
+        let record = FileStore::decode(hash);
+        self.record_mut(pos).copy_from_slice(&record);
+    }
+
+    /// The file is truncated or extended to the exact size of the records and mapped again. Panics if the file can't
+    /// be resized, the same way a vector does when there is no memory left
+    fn resize(&mut self, len: usize, hash: &str) {
+        let record = FileStore::decode(hash);
+        let old_len = self.len;
+
+        self.map = None;
+        self.file
+            .set_len((HEADER_SIZE + len * RECORD_SIZE) as u64)
+            .expect("could not resize the node file");
+        self.len = len;
+        self.map().expect("could not map the node file");
+
+        // The file is extended with zeros, so only other hashes need to be written
+        if record != [0; RECORD_SIZE] {
+            for pos in old_len..len {
+                self.record_mut(pos).copy_from_slice(&record);
+            }
+        }
+    }
+
+    fn holds(&self, hash: &str) -> bool {
+        hash.is_empty()
+            || (hash.len() == 2 * RECORD_SIZE && hash.bytes().all(|b| b.is_ascii_hexdigit()))
+    }
+
+    fn leaves_len(&self) -> Option<usize> {
+        let header = self.header();
+        let len = u64::from_le_bytes(header[FILE_MAGIC.len()..].try_into().ok()?);
+        usize::try_from(len).ok()
+    }
+
+    fn set_leaves_len(&mut self, len: usize) {
+        self.header_mut()[FILE_MAGIC.len()..].copy_from_slice(&(len as u64).to_le_bytes());
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &self.map {
+            Some(map) => map.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FileStore, MemoryStore, NodeStore, HEADER_SIZE};
+    use crate::errors::MerkleTreeErrors;
+    use crate::merkle_tree::{MerkleTree, Padding};
+
+    const HASH: &str = "ca978112ca1bbdcafac231b39a23dc4da786eff8147c4e72b9807785afee48bb";
+
+    #[test]
+    fn test_01_file_store_keeps_fixed_size_records() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nodes");
+        let mut store = FileStore::create(&path).unwrap();
+
+        store.resize(3, HASH);
+        store.set(1, "");
+        store.flush().unwrap();
+
+        assert_eq!(3, store.len());
+        assert_eq!(HASH, store.get(0));
+        assert_eq!("0".repeat(64), store.get(1));
+        assert_eq!(
+            (HEADER_SIZE + 96) as u64,
+            std::fs::metadata(&path).unwrap().len()
+        );
+        assert_eq!(
+            hex::decode(HASH).unwrap(),
+            std::fs::read(&path).unwrap()[HEADER_SIZE + 64..]
+        );
+
+        store.clear();
+        assert!(store.is_empty());
+        assert_eq!(HEADER_SIZE as u64, std::fs::metadata(&path).unwrap().len());
+    }
+
+    #[test]
+    fn test_02_trees_are_the_same_in_memory_and_in_a_file() {
+        let dir = tempfile::tempdir().unwrap();
+
+        for padding in [Padding::DuplicateLast, Padding::Zero, Padding::Promote] {
+            let store = FileStore::create(dir.path().join(format!("{padding:?}"))).unwrap();
+            let mut on_disk = MerkleTree::new()
+                .with_padding(padding)
+                .with_store(store)
+                .unwrap();
+            let mut in_memory = MerkleTree::new().with_padding(padding);
+
+            for i in 0..37 {
+                on_disk.add_unhashed(i.to_string());
+                in_memory.add_unhashed(i.to_string());
+                assert_eq!(in_memory.root(), on_disk.root());
+            }
+            for index in 0..37 {
+                let proof = on_disk.generate_proof(index);
+                assert_eq!(in_memory.generate_proof(index), proof);
+                assert!(on_disk.verify(proof, in_memory.leaf(index).unwrap(), index));
+            }
+        }
+    }
+
+    #[test]
+    fn test_03_existing_nodes_are_moved_to_the_new_store() {
+        let dir = tempfile::tempdir().unwrap();
+        let tree = MerkleTree::build(vec!["a", "b", "c"], true);
+        let root = tree.root();
+
+        let tree = tree
+            .with_store(FileStore::create(dir.path().join("nodes")).unwrap())
+            .unwrap();
+        assert_eq!(root, tree.root());
+        assert_eq!(3, tree.len());
+
+        let tree = tree.with_store(MemoryStore::default()).unwrap();
+        assert_eq!(root, tree.root());
+    }
+
+    #[test]
+    #[should_panic(expected = "only holds 32 byte hashes")]
+    fn test_04_file_store_rejects_values_that_are_not_hashes() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = FileStore::create(dir.path().join("nodes")).unwrap();
+        store.resize(1, "");
+        store.set(0, "ab");
+    }
+
+    #[test]
+    fn test_05_file_trees_refuse_leaves_that_are_not_hashes() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = FileStore::create(dir.path().join("nodes")).unwrap();
+        let mut tree = MerkleTree::new().with_store(store).unwrap();
+
+        tree.add_unhashed("a".to_string());
+        assert!(tree.try_add("aññ".to_string()).is_err());
+        assert!(tree
+            .try_extend(vec![HASH.to_string(), "ab".to_string()])
+            .is_err());
+        assert_eq!(1, tree.len());
+        tree.try_add(HASH.to_string()).unwrap();
+        assert_eq!(Some(HASH.to_string()), tree.leaf(1));
+
+        let mut texts = MerkleTree::new();
+        texts.add("not a hash".to_string());
+        let store = FileStore::create(dir.path().join("texts")).unwrap();
+        assert!(matches!(
+            texts.with_store(store),
+            Err(MerkleTreeErrors::UnsupportedHashError(_))
+        ));
+    }
+
+    #[test]
+    fn test_06_file_trees_are_opened_again() {
+        let dir = tempfile::tempdir().unwrap();
+
+        for padding in [Padding::DuplicateLast, Padding::Zero, Padding::Promote] {
+            let path = dir.path().join(format!("{padding:?}"));
+            let mut in_memory = MerkleTree::new().with_padding(padding);
+            let mut on_disk = MerkleTree::new()
+                .with_padding(padding)
+                .with_store(FileStore::create(&path).unwrap())
+                .unwrap();
+            for i in 0..21 {
+                on_disk.add_unhashed(i.to_string());
+                in_memory.add_unhashed(i.to_string());
+            }
+            on_disk.flush().unwrap();
+            drop(on_disk);
+
+            let mut reopened = MerkleTree::new()
+                .with_padding(padding)
+                .with_leaf_index()
+                .with_existing_store(FileStore::open(&path).unwrap())
+                .unwrap();
+            assert_eq!(21, reopened.len());
+            assert_eq!(in_memory.root(), reopened.root());
+            assert_eq!(in_memory.leaves(), reopened.leaves());
+            assert_eq!(Some(20), reopened.index_of_unhashed("20"));
+
+            for i in 21..40 {
+                reopened.add_unhashed(i.to_string());
+                in_memory.add_unhashed(i.to_string());
+            }
+            assert_eq!(in_memory.root(), reopened.root());
+        }
+
+        let empty = dir.path().join("empty");
+        drop(FileStore::create(&empty).unwrap());
+        let tree = MerkleTree::new()
+            .with_existing_store(FileStore::open(&empty).unwrap())
+            .unwrap();
+        assert!(tree.is_empty());
+
+        std::fs::write(dir.path().join("other"), [1; 48]).unwrap();
+        assert!(FileStore::open(dir.path().join("other")).is_err());
+        assert!(matches!(
+            MerkleTree::new().with_existing_store(MemoryStore::default()),
+            Err(MerkleTreeErrors::InconsistentStoreError(_))
+        ));
+    }
+}
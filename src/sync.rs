@@ -0,0 +1,589 @@
+use std::io::{ErrorKind, Read, Write};
+
+use crate::errors::SyncErrors;
+use crate::merkle_tree::MerkleTree;
+use crate::proof;
+
+/// The messages both sides exchange. Each one goes as a tag byte followed by its fields: numbers as little endian
+/// and strings and lists with their length before them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Message {
+    /// Asks for the hashes of the children of the node at a level (0 is the root) and index of the other tree
+    GetNode { level: u32, index: u64 },
+    /// Answer to GetNode, with the size, depth and root of the tree so the first one tells what the leaves must lead
+    /// to. A missing child, or the root of a tree without leaves, is an empty string
+    NodeHashes {
+        tree_size: u64,
+        depth: u32,
+        root: String,
+        hashes: Vec<String>,
+    },
+    /// Asks for an amount of consecutive leaves
+    GetLeaves { first: u64, amount: u64 },
+    /// Answer to GetLeaves. It has less leaves than asked if the tree ends before
+    Leaves { first: u64, leaves: Vec<String> },
+    /// Ends the session
+    Done,
+}
+
+const GET_NODE: u8 = 0;
+const NODE_HASHES: u8 = 1;
+const GET_LEAVES: u8 = 2;
+const LEAVES: u8 = 3;
+const DONE: u8 = 4;
+
+impl Message {
+    pub fn write_to(&self, writer: &mut impl Write) -> Result<(), SyncErrors> {
+        let mut bytes = Vec::new();
+        match self {
+            Message::GetNode { level, index } => {
+                bytes.push(GET_NODE);
+                bytes.extend_from_slice(&level.to_le_bytes());
+                bytes.extend_from_slice(&index.to_le_bytes());
+            }
+            Message::NodeHashes {
+                tree_size,
+                depth,
+                root,
+                hashes,
+            } => {
+                bytes.push(NODE_HASHES);
+                bytes.extend_from_slice(&tree_size.to_le_bytes());
+                bytes.extend_from_slice(&depth.to_le_bytes());
+                write_string(&mut bytes, root);
+                write_strings(&mut bytes, hashes);
+            }
+            Message::GetLeaves { first, amount } => {
+                bytes.push(GET_LEAVES);
+                bytes.extend_from_slice(&first.to_le_bytes());
+                bytes.extend_from_slice(&amount.to_le_bytes());
+            }
+            Message::Leaves { first, leaves } => {
+                bytes.push(LEAVES);
+                bytes.extend_from_slice(&first.to_le_bytes());
+                write_strings(&mut bytes, leaves);
+            }
+            Message::Done => bytes.push(DONE),
+        }
+
+        writer.write_all(&bytes)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Returns None if the stream ends before a new message starts
+    pub fn read_from(reader: &mut impl Read) -> Result<Option<Self>, SyncErrors> {
+        let mut tag = [0; 1];
+        match reader.read_exact(&mut tag) {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e.into()),
+        }
+
+        let message = match tag[0] {
+            GET_NODE => Message::GetNode {
+                level: read_u32(reader)?,
+                index: read_u64(reader)?,
+            },
+            NODE_HASHES => Message::NodeHashes {
+                tree_size: read_u64(reader)?,
+                depth: read_u32(reader)?,
+                root: read_string(reader)?,
+                hashes: read_strings(reader)?,
+            },
+            GET_LEAVES => Message::GetLeaves {
+                first: read_u64(reader)?,
+                amount: read_u64(reader)?,
+            },
+            LEAVES => Message::Leaves {
+                first: read_u64(reader)?,
+                leaves: read_strings(reader)?,
+            },
+            DONE => Message::Done,
+            other => {
+                return Err(SyncErrors::MalformedMessageError(format!(
+                    "unknown message {other}"
+                )));
+            }
+        };
+        Ok(Some(message))
+    }
+}
+
+fn write_string(bytes: &mut Vec<u8>, string: &str) {
+    bytes.extend_from_slice(&(string.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(string.as_bytes());
+}
+
+fn write_strings(bytes: &mut Vec<u8>, strings: &[String]) {
+    bytes.extend_from_slice(&(strings.len() as u32).to_le_bytes());
+    for string in strings {
+        write_string(bytes, string);
+    }
+}
+
+/// Inside a message, the stream ending is an error
+fn read_exact(reader: &mut impl Read, buffer: &mut [u8]) -> Result<(), SyncErrors> {
+    reader.read_exact(buffer).map_err(|e| match e.kind() {
+        ErrorKind::UnexpectedEof => {
+            SyncErrors::MalformedMessageError("the stream ended inside a message".to_string())
+        }
+        _ => e.into(),
+    })
+}
+
+fn read_u32(reader: &mut impl Read) -> Result<u32, SyncErrors> {
+    let mut bytes = [0; 4];
+    read_exact(reader, &mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64(reader: &mut impl Read) -> Result<u64, SyncErrors> {
+    let mut bytes = [0; 8];
+    read_exact(reader, &mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+/// The length comes from the other side, so nothing is allocated before the bytes actually arrive
+fn read_string(reader: &mut impl Read) -> Result<String, SyncErrors> {
+    let len = read_u32(reader)? as u64;
+    let mut string = Vec::new();
+    reader.take(len).read_to_end(&mut string)?;
+    if string.len() as u64 != len {
+        return Err(SyncErrors::MalformedMessageError(
+            "the stream ended inside a message".to_string(),
+        ));
+    }
+    String::from_utf8(string)
+        .map_err(|_| SyncErrors::MalformedMessageError("a hash is not valid text".to_string()))
+}
+
+fn read_strings(reader: &mut impl Read) -> Result<Vec<String>, SyncErrors> {
+    let amount = read_u32(reader)?;
+    let mut strings = Vec::new();
+    for _ in 0..amount {
+        strings.push(read_string(reader)?);
+    }
+    Ok(strings)
+}
+
+/// Answers the requests of the other side until it sends Done or closes the stream
+pub fn serve(
+    tree: &MerkleTree,
+    mut reader: impl Read,
+    mut writer: impl Write,
+) -> Result<(), SyncErrors> {
+    loop {
+        let answer = match Message::read_from(&mut reader)? {
+            None | Some(Message::Done) => return Ok(()),
+            Some(Message::GetNode { level, index }) => {
+                let left = index.saturating_mul(2);
+                let hashes = [left, left.saturating_add(1)]
+                    .into_iter()
+                    .map(|child| {
+                        tree.node(level.saturating_add(1), child as usize)
+                            .unwrap_or_default()
+                    })
+                    .collect();
+                Message::NodeHashes {
+                    tree_size: tree.len() as u64,
+                    depth: tree.depth(),
+                    root: tree.root().unwrap_or_default(),
+                    hashes,
+                }
+            }
+            Some(Message::GetLeaves { first, amount }) => {
+                let last = first.saturating_add(amount).min(tree.len() as u64);
+                Message::Leaves {
+                    first,
+                    leaves: (first..last)
+                        .filter_map(|index| tree.leaf(index as usize))
+                        .collect(),
+                }
+            }
+            Some(other) => {
+                return Err(SyncErrors::UnexpectedMessageError(format!("{other:?}")));
+            }
+        };
+        answer.write_to(&mut writer)?;
+    }
+}
+
+/// Makes the leaves of the tree equal to the ones of the tree served at the other side. Both trees are compared from
+/// the root, only asking for the children of the nodes whose hashes differ, and the leaves the other tree has after
+/// the last one of this tree are asked all at once. The leaves this tree has after the last one of the other are
+/// removed. Both trees must have the same padding and domain separation: if the leaves don't lead to the root of the
+/// other tree, the tree is left as it was and an error is returned. Returns the indexes of the leaves that changed or
+/// were added
+pub fn sync(
+    tree: &mut MerkleTree,
+    reader: impl Read,
+    writer: impl Write,
+) -> Result<Vec<usize>, SyncErrors> {
+    let mut session = Session {
+        tree,
+        reader,
+        writer,
+        depth: 0,
+        shared: 0,
+        leaves: Vec::new(),
+        changed: Vec::new(),
+    };
+
+    let (tree_size, depth, root, root_children) = session.get_node(0, 0)?;
+    let expected_depth = if tree_size == 0 {
+        0
+    } else if tree_size > isize::MAX as usize {
+        u32::MAX
+    } else {
+        proof::depth_of(tree_size)
+    };
+    if depth != expected_depth {
+        return Err(SyncErrors::UnexpectedMessageError(format!(
+            "a tree of {tree_size} leaves can't have depth {depth}"
+        )));
+    }
+    session.depth = depth;
+    session.leaves = session.tree.leaves();
+    session.leaves.truncate(tree_size);
+    session.shared = session.leaves.len();
+    if depth > 0 {
+        session.compare_children(0, 0, root_children)?;
+    }
+
+    if tree_size > session.shared {
+        let first = session.shared;
+        let new_leaves = session.get_leaves(first, tree_size - first)?;
+        session.changed.extend(first..first + new_leaves.len());
+        session.leaves.extend(new_leaves);
+    }
+    Message::Done.write_to(&mut session.writer)?;
+
+    let Session {
+        tree,
+        leaves,
+        changed,
+        ..
+    } = session;
+    if let Some(&index) = changed
+        .iter()
+        .find(|&&index| !tree.can_hold(&leaves[index]))
+    {
+        return Err(SyncErrors::UnexpectedMessageError(format!(
+            "the tree can't hold the leaf {:?}",
+            leaves[index]
+        )));
+    }
+    let previous_leaves = if leaves.len() != tree.len() || !changed.is_empty() {
+        let previous_leaves = tree.leaves();
+        tree.build_from_leaves(leaves);
+        Some(previous_leaves)
+    } else {
+        None
+    };
+
+    if tree.root().unwrap_or_default() != root {
+        if let Some(previous_leaves) = previous_leaves {
+            tree.build_from_leaves(previous_leaves);
+        }
+        return Err(SyncErrors::UnexpectedMessageError(
+            "the leaves don't lead to the root of the other tree".to_string(),
+        ));
+    }
+    Ok(changed)
+}
+
+struct Session<'a, R, W> {
+    tree: &'a mut MerkleTree,
+    reader: R,
+    writer: W,
+    /// Depth of the tree of the other side
+    depth: u32,
+    /// Amount of leaves both trees have
+    shared: usize,
+    /// The leaves of this tree as they are being fixed
+    leaves: Vec<String>,
+    changed: Vec<usize>,
+}
+
+impl<R: Read, W: Write> Session<'_, R, W> {
+    fn get_node(
+        &mut self,
+        level: u32,
+        index: usize,
+    ) -> Result<(usize, u32, String, Vec<String>), SyncErrors> {
+        let request = Message::GetNode {
+            level,
+            index: index as u64,
+        };
+        request.write_to(&mut self.writer)?;
+        match self.receive()? {
+            Message::NodeHashes {
+                tree_size,
+                depth,
+                root,
+                hashes,
+            } if hashes.len() == 2 => Ok((tree_size as usize, depth, root, hashes)),
+            other => Err(SyncErrors::UnexpectedMessageError(format!("{other:?}"))),
+        }
+    }
+
+    fn get_leaves(&mut self, first: usize, amount: usize) -> Result<Vec<String>, SyncErrors> {
+        let request = Message::GetLeaves {
+            first: first as u64,
+            amount: amount as u64,
+        };
+        request.write_to(&mut self.writer)?;
+        match self.receive()? {
+            Message::Leaves {
+                first: answered,
+                leaves,
+            } if answered == first as u64 && leaves.len() == amount => Ok(leaves),
+            other => Err(SyncErrors::UnexpectedMessageError(format!("{other:?}"))),
+        }
+    }
+
+    fn receive(&mut self) -> Result<Message, SyncErrors> {
+        Message::read_from(&mut self.reader)?.ok_or_else(|| {
+            SyncErrors::MalformedMessageError("the other side closed the stream".to_string())
+        })
+    }
+
+    /// Goes down into the children of the node at (level, index) of the other tree whose hashes differ from the
+    /// same subtree of this one. The leaves after the shared ones are left to be asked at once
+    fn compare_children(
+        &mut self,
+        level: u32,
+        index: usize,
+        hashes: Vec<String>,
+    ) -> Result<(), SyncErrors> {
+        let height = self.depth - level - 1;
+        for (child, hash) in [2 * index, 2 * index + 1].into_iter().zip(hashes) {
+            if child << height >= self.shared {
+                continue;
+            }
+
+            if height == 0 {
+                if self.leaves[child] != hash {
+                    self.leaves[child] = hash;
+                    self.changed.push(child);
+                }
+                continue;
+            }
+
+            // Subtrees with padding in one of the trees can have the same hash with different leaves
+            let only_shared_leaves = (child + 1) << height <= self.shared;
+            if only_shared_leaves && self.tree.subtree_hash(height, child) == Some(hash) {
+                continue;
+            }
+            let (_, _, _, grandchildren) = self.get_node(level + 1, child)?;
+            self.compare_children(level + 1, child, grandchildren)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use std::thread;
+
+    use super::{serve, sync, Message};
+    use crate::errors::SyncErrors;
+    use crate::hashing::{DomainSeparation, NodeEncoding};
+    use crate::merkle_tree::{MerkleTree, Padding};
+
+    fn tree_of(texts: &[String]) -> MerkleTree {
+        MerkleTree::build(texts.iter().map(|t| t.as_str()).collect(), true)
+    }
+
+    /// Runs both sides in two threads connected by two pipes, and returns the synced tree and the changes
+    fn sync_with_pipes(mut replica: MerkleTree, source: MerkleTree) -> (MerkleTree, Vec<usize>) {
+        let (to_server, server_input) = io::pipe().unwrap();
+        let (server_output, from_server) = io::pipe().unwrap();
+
+        let server = thread::spawn(move || serve(&source, to_server, from_server).unwrap());
+        let changed = sync(&mut replica, server_output, server_input).unwrap();
+        server.join().unwrap();
+
+        (replica, changed)
+    }
+
+    #[test]
+    fn test_01_messages_are_read_back() {
+        let messages = [
+            Message::GetNode { level: 3, index: 5 },
+            Message::NodeHashes {
+                tree_size: 7,
+                depth: 3,
+                root: "ef".to_string(),
+                hashes: vec!["ab".to_string(), "".to_string()],
+            },
+            Message::GetLeaves {
+                first: 4,
+                amount: 2,
+            },
+            Message::Leaves {
+                first: 4,
+                leaves: vec!["cd".to_string()],
+            },
+            Message::Done,
+        ];
+
+        let mut bytes = Vec::new();
+        for message in &messages {
+            message.write_to(&mut bytes).unwrap();
+        }
+        let mut reader = bytes.as_slice();
+        for message in messages {
+            assert_eq!(Some(message), Message::read_from(&mut reader).unwrap());
+        }
+        assert_eq!(None, Message::read_from(&mut reader).unwrap());
+
+        assert!(Message::read_from(&mut [9u8].as_slice()).is_err());
+        assert!(Message::read_from(&mut [0u8, 1, 0].as_slice()).is_err());
+    }
+
+    #[test]
+    fn test_02_replicas_get_the_changed_and_new_leaves() {
+        let texts: Vec<String> = (0..50).map(|i| i.to_string()).collect();
+        let mut replica_texts = texts[..30].to_vec();
+        replica_texts[4] = "stale".to_string();
+        replica_texts[17] = "stale".to_string();
+
+        let source = tree_of(&texts);
+        let root = source.root();
+        let (replica, changed) = sync_with_pipes(tree_of(&replica_texts), source);
+
+        let expected: Vec<usize> = [4, 17].into_iter().chain(30..50).collect();
+        assert_eq!(expected, changed);
+        assert_eq!(root, replica.root());
+    }
+
+    #[test]
+    fn test_03_extra_leaves_of_the_replica_are_removed() {
+        let texts: Vec<String> = (0..20).map(|i| i.to_string()).collect();
+        let source = tree_of(&texts[..9]).with_padding(Padding::Promote);
+        let replica = tree_of(&texts).with_padding(Padding::Promote);
+        let root = source.root();
+
+        let (replica, changed) = sync_with_pipes(replica, source);
+        assert!(changed.is_empty());
+        assert_eq!(9, replica.len());
+        assert_eq!(root, replica.root());
+
+        let (replica, _) = sync_with_pipes(replica, MerkleTree::new());
+        assert!(replica.is_empty());
+        let (replica, changed) =
+            sync_with_pipes(replica, tree_of(&texts).with_padding(Padding::Promote));
+        assert_eq!((0..20).collect::<Vec<_>>(), changed);
+        assert_eq!(20, replica.len());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_04_trees_are_synced_over_a_unix_socket() {
+        use std::os::unix::net::UnixStream;
+
+        let texts: Vec<String> = (0..64).map(|i| i.to_string()).collect();
+        let mut changed_texts = texts.clone();
+        changed_texts[63] = "changed".to_string();
+        let source = tree_of(&changed_texts);
+        let root = source.root();
+
+        let (client, server) = UnixStream::pair().unwrap();
+        let server = thread::spawn(move || serve(&source, &server, &server).unwrap());
+
+        let mut replica = tree_of(&texts);
+        assert_eq!(vec![63], sync(&mut replica, &client, &client).unwrap());
+        server.join().unwrap();
+        assert_eq!(root, replica.root());
+    }
+
+    #[test]
+    fn test_05_indexed_replicas_find_the_synced_leaves() {
+        let texts: Vec<String> = (0..20).map(|i| i.to_string()).collect();
+        let mut replica_texts = texts[..10].to_vec();
+        replica_texts[3] = "stale".to_string();
+        let source = tree_of(&texts);
+        let replica = tree_of(&replica_texts).with_leaf_index();
+
+        let (replica, _) = sync_with_pipes(replica, source);
+        assert_eq!(Some(3), replica.index_of_unhashed("3"));
+        assert_eq!(Some(15), replica.index_of_unhashed("15"));
+        assert_eq!(None, replica.index_of_unhashed("stale"));
+    }
+
+    #[test]
+    fn test_06_trees_that_dont_lead_to_the_same_root_are_not_synced() {
+        let texts: Vec<String> = (0..9).map(|i| i.to_string()).collect();
+        let replica_texts = texts[..5].to_vec();
+
+        // Same leaves, but the source pads with the promote padding
+        for replica_texts in [&texts, &replica_texts] {
+            let source = tree_of(&texts).with_padding(Padding::Promote);
+            let (to_server, server_input) = io::pipe().unwrap();
+            let (server_output, from_server) = io::pipe().unwrap();
+            let server = thread::spawn(move || serve(&source, to_server, from_server).unwrap());
+
+            let mut replica = tree_of(replica_texts);
+            let root = replica.root();
+            assert!(sync(&mut replica, server_output, server_input).is_err());
+            server.join().unwrap();
+            assert_eq!(root, replica.root());
+            assert_eq!(replica_texts.len(), replica.len());
+        }
+    }
+
+    #[test]
+    fn test_07_answers_with_less_leaves_than_asked_are_rejected() {
+        let texts: Vec<String> = (0..4).map(|i| i.to_string()).collect();
+        let source = tree_of(&texts);
+
+        // Answers as the source, but leaves out the last of the leaves asked
+        let mut answers = Vec::new();
+        let mut requests = Vec::new();
+        Message::GetNode { level: 0, index: 0 }
+            .write_to(&mut requests)
+            .unwrap();
+        serve(&source, requests.as_slice(), &mut answers).unwrap();
+        Message::Leaves {
+            first: 0,
+            leaves: source.leaves()[..3].to_vec(),
+        }
+        .write_to(&mut answers)
+        .unwrap();
+
+        let mut replica = MerkleTree::new();
+        assert!(matches!(
+            sync(&mut replica, answers.as_slice(), io::sink()),
+            Err(SyncErrors::UnexpectedMessageError(answer)) if answer.starts_with("Leaves")
+        ));
+        assert!(replica.is_empty());
+    }
+
+    #[test]
+    fn test_08_replicas_hashing_differently_are_not_synced() {
+        let texts: Vec<String> = (0..6).map(|i| i.to_string()).collect();
+
+        // Same leaves, with other tags or the children fed as bytes
+        for domain in [
+            DomainSeparation::rfc6962(),
+            DomainSeparation::default().with_node_encoding(NodeEncoding::Raw),
+        ] {
+            let source = tree_of(&texts);
+            let (to_server, server_input) = io::pipe().unwrap();
+            let (server_output, from_server) = io::pipe().unwrap();
+            let server = thread::spawn(move || serve(&source, to_server, from_server).unwrap());
+
+            let mut replica = tree_of(&texts).with_domain_separation(domain);
+            let root = replica.root();
+            assert!(matches!(
+                sync(&mut replica, server_output, server_input),
+                Err(SyncErrors::UnexpectedMessageError(reason)) if reason.contains("root")
+            ));
+            server.join().unwrap();
+            assert_eq!(root, replica.root());
+        }
+    }
+}
@@ -0,0 +1,471 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use crate::errors::{MerkleTreeErrors, WalErrors};
+use crate::hashing;
+use crate::merkle_tree::MerkleTree;
+use crate::snapshot::MerkleSnapshot;
+
+const LOG_FILE: &str = "log";
+const CHECKPOINT_FILE: &str = "checkpoint";
+/// The checkpoint is written here first and then renamed, so a crash never leaves half a checkpoint
+const CHECKPOINT_TMP_FILE: &str = "checkpoint.tmp";
+const CHECKPOINT_MAGIC: &[u8; 4] = b"MTCK";
+const DEFAULT_CHECKPOINT_INTERVAL: usize = 1000;
+
+/// A MerkleTree whose adds are saved to a log in a directory before being applied, so it can be recovered after a
+/// crash. Each entry of the log is the length of the leaf, its CRC32 and the leaf itself. Every some adds, all the
+/// leaves are saved in a checkpoint together with the root and the position of the log they reach, so the recovery
+/// only replays the entries after it
+pub struct DurableMerkleTree {
+    tree: MerkleTree,
+    dir: PathBuf,
+    log: File,
+    /// Bytes of the log with complete entries
+    log_len: u64,
+    adds_since_checkpoint: usize,
+    checkpoint_interval: usize,
+    /// Why the last periodic checkpoint failed, until it is taken
+    checkpoint_error: Option<WalErrors>,
+}
+
+impl DurableMerkleTree {
+    /// Opens the log of the directory (creating both if needed) and recovers the tree: first the leaves of the last
+    /// checkpoint, checking they lead to its root, and then the entries of the log after it. A torn or corrupted
+    /// entry at the end of the log is the trace of a crash in the middle of an add, so the log is truncated there.
+    /// A corrupted entry with more of the log after it can't come from a crash, so it's an error instead of
+    /// dropping the entries that follow. The given tree is emptied and refilled, it must have the padding and domain separation used to write the log
+    pub fn open(dir: impl AsRef<Path>, mut tree: MerkleTree) -> Result<Self, WalErrors> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+        tree.clear();
+
+        let mut log_len = 0;
+        if let Some(checkpoint) = read_checkpoint(&dir.join(CHECKPOINT_FILE))? {
+            tree.build_from_leaves(checkpoint.leaves);
+            let root = tree.root().unwrap_or_default();
+            if root != checkpoint.root {
+                return Err(WalErrors::RootMismatchError(format!(
+                    "the checkpoint has root {:?} but its leaves lead to {root:?}",
+                    checkpoint.root
+                )));
+            }
+            log_len = checkpoint.log_offset;
+        }
+
+        let mut log = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(dir.join(LOG_FILE))?;
+        let file_len = log.metadata()?.len();
+        if log_len > file_len {
+            return Err(WalErrors::CorruptCheckpointError(format!(
+                "the checkpoint reaches byte {log_len} of a log of {file_len} bytes"
+            )));
+        }
+
+        log.seek(SeekFrom::Start(log_len))?;
+        let mut reader = BufReader::new(&log);
+        let mut replayed = Vec::new();
+        loop {
+            match read_entry(&mut reader)? {
+                Entry::Leaf(leaf) => {
+                    log_len += entry_len(&leaf);
+                    replayed.push(leaf);
+                }
+                Entry::End => break,
+                Entry::Damaged { len } if log_len + len >= file_len => break,
+                Entry::Damaged { .. } => {
+                    return Err(WalErrors::CorruptLogError(format!(
+                        "the entry at byte {log_len} is damaged and the log goes on after it"
+                    )));
+                }
+            }
+        }
+        // Built at once instead of adding each leaf, which rehashes the padding leaves copied on every add
+        let adds_since_checkpoint = replayed.len();
+        if !replayed.is_empty() {
+            let mut leaves = tree.leaves();
+            leaves.extend(replayed);
+            tree.build_from_leaves(leaves);
+        }
+        if log_len < file_len {
+            log.set_len(log_len)?;
+            log.sync_all()?;
+        }
+
+        Ok(DurableMerkleTree {
+            tree,
+            dir,
+            log,
+            log_len,
+            adds_since_checkpoint,
+            checkpoint_interval: DEFAULT_CHECKPOINT_INTERVAL,
+            checkpoint_error: None,
+        })
+    }
+
+    /// Amount of adds after which a checkpoint is written
+    pub fn with_checkpoint_interval(mut self, adds: usize) -> Self {
+        self.checkpoint_interval = adds.max(1);
+        self
+    }
+
+    /// The tree is only changed once the entry is safely in the log. Once it is, the add succeeds even if the
+    /// periodic checkpoint fails, so retrying doesn't add the leaf twice: see take_checkpoint_error
+    pub fn add(&mut self, hashed_string: String) -> Result<(), WalErrors> {
+        if !self.tree.can_hold(&hashed_string) {
+            return Err(MerkleTreeErrors::UnsupportedHashError(hashed_string).into());
+        }
+        let entry = encode_entry(&hashed_string);
+        let written = self
+            .log
+            .write_all(&entry)
+            .and_then(|_| self.log.sync_data());
+        if let Err(e) = written {
+            // Half an entry would hide the ones written after it on recovery
+            let _ = self.log.set_len(self.log_len);
+            return Err(e.into());
+        }
+        self.log_len += entry.len() as u64;
+
+        self.tree.add(hashed_string);
+        self.adds_since_checkpoint += 1;
+        if self.adds_since_checkpoint >= self.checkpoint_interval {
+            // Without the checkpoint the log is only longer to replay, and it's tried again on the next add
+            self.checkpoint_error = self.checkpoint().err();
+        }
+        Ok(())
+    }
+
+    /// Why the last periodic checkpoint made by add failed, if it did. Later adds keep trying it until one succeeds
+    pub fn take_checkpoint_error(&mut self) -> Option<WalErrors> {
+        self.checkpoint_error.take()
+    }
+
+    pub fn add_unhashed(&mut self, unhashed_text: String) -> Result<(), WalErrors> {
+        let hashed_string =
+            hashing::hash_leaf(self.tree.domain_separation(), unhashed_text.as_bytes());
+        self.add(hashed_string)
+    }
+
+    /// Saves all the leaves and the root, so the next recovery doesn't need to replay the log until here
+    pub fn checkpoint(&mut self) -> Result<(), WalErrors> {
+        let checkpoint = encode_checkpoint(
+            self.log_len,
+            &self.tree.root().unwrap_or_default(),
+            &self.tree.leaves(),
+        );
+
+        let tmp_path = self.dir.join(CHECKPOINT_TMP_FILE);
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(&checkpoint)?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, self.dir.join(CHECKPOINT_FILE))?;
+        if cfg!(unix) {
+            // The rename is only durable once the directory is synced
+            File::open(&self.dir)?.sync_all()?;
+        }
+
+        self.adds_since_checkpoint = 0;
+        Ok(())
+    }
+
+    /// The recovered tree. It can't be changed directly, as the changes wouldn't go to the log
+    pub fn tree(&self) -> &MerkleTree {
+        &self.tree
+    }
+
+    pub fn snapshot(&mut self) -> MerkleSnapshot {
+        self.tree.snapshot()
+    }
+}
+
+fn encode_entry(leaf: &str) -> Vec<u8> {
+    let mut entry = Vec::with_capacity(entry_len(leaf) as usize);
+    entry.extend_from_slice(&(leaf.len() as u32).to_le_bytes());
+    entry.extend_from_slice(&crc32fast::hash(leaf.as_bytes()).to_le_bytes());
+    entry.extend_from_slice(leaf.as_bytes());
+    entry
+}
+
+/// Length, checksum and leaf
+fn entry_len(leaf: &str) -> u64 {
+    8 + leaf.len() as u64
+}
+
+enum Entry {
+    Leaf(String),
+    /// The log ends here, or in the middle of the length and checksum of an entry
+    End,
+    /// The leaf doesn't match its checksum or is cut short. The length of the whole entry is the one it claims
+    Damaged {
+        len: u64,
+    },
+}
+
+fn read_entry(reader: &mut impl Read) -> io::Result<Entry> {
+    let mut header = [0; 8];
+    if !read_or_eof(reader, &mut header)? {
+        return Ok(Entry::End);
+    }
+    let len = u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
+    let checksum = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+
+    let mut leaf = Vec::new();
+    reader.take(len as u64).read_to_end(&mut leaf)?;
+    if leaf.len() != len as usize || crc32fast::hash(&leaf) != checksum {
+        return Ok(Entry::Damaged {
+            len: 8 + len as u64,
+        });
+    }
+    Ok(match String::from_utf8(leaf) {
+        Ok(leaf) => Entry::Leaf(leaf),
+        Err(_) => Entry::Damaged {
+            len: 8 + len as u64,
+        },
+    })
+}
+
+/// Returns false if the reader ends before filling the buffer
+fn read_or_eof(reader: &mut impl Read, buffer: &mut [u8]) -> io::Result<bool> {
+    match reader.read_exact(buffer) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+struct Checkpoint {
+    log_offset: u64,
+    root: String,
+    leaves: Vec<String>,
+}
+
+/// Magic, position of the log, amount of leaves, root, leaves, and the CRC32 of all of it. The strings go with their
+/// length before them
+fn encode_checkpoint(log_offset: u64, root: &str, leaves: &[String]) -> Vec<u8> {
+    let mut checkpoint = CHECKPOINT_MAGIC.to_vec();
+    checkpoint.extend_from_slice(&log_offset.to_le_bytes());
+    checkpoint.extend_from_slice(&(leaves.len() as u64).to_le_bytes());
+    for string in std::iter::once(root).chain(leaves.iter().map(|leaf| leaf.as_str())) {
+        checkpoint.extend_from_slice(&(string.len() as u32).to_le_bytes());
+        checkpoint.extend_from_slice(string.as_bytes());
+    }
+    let checksum = crc32fast::hash(&checkpoint);
+    checkpoint.extend_from_slice(&checksum.to_le_bytes());
+    checkpoint
+}
+
+/// None if there is no checkpoint yet. Unlike the log, a damaged checkpoint is an error, as it's replaced atomically
+fn read_checkpoint(path: &Path) -> Result<Option<Checkpoint>, WalErrors> {
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let corrupt = |reason: &str| WalErrors::CorruptCheckpointError(reason.to_string());
+
+    let Some((content, checksum)) = bytes.split_last_chunk::<4>() else {
+        return Err(corrupt("too short"));
+    };
+    if crc32fast::hash(content) != u32::from_le_bytes(*checksum) {
+        return Err(corrupt("wrong checksum"));
+    }
+
+    let mut reader = content;
+    let mut magic = [0; 4];
+    let mut number = [0; 8];
+    reader
+        .read_exact(&mut magic)
+        .map_err(|_| corrupt("too short"))?;
+    if &magic != CHECKPOINT_MAGIC {
+        return Err(corrupt("not a checkpoint"));
+    }
+    reader
+        .read_exact(&mut number)
+        .map_err(|_| corrupt("too short"))?;
+    let log_offset = u64::from_le_bytes(number);
+    reader
+        .read_exact(&mut number)
+        .map_err(|_| corrupt("too short"))?;
+    let leaves_amount = u64::from_le_bytes(number);
+
+    let mut read_string = || -> Result<String, WalErrors> {
+        let mut len = [0; 4];
+        reader
+            .read_exact(&mut len)
+            .map_err(|_| corrupt("too short"))?;
+        let mut string = vec![0; u32::from_le_bytes(len) as usize];
+        reader
+            .read_exact(&mut string)
+            .map_err(|_| corrupt("too short"))?;
+        String::from_utf8(string).map_err(|_| corrupt("a leaf is not valid text"))
+    };
+    let root = read_string()?;
+    let leaves = (0..leaves_amount)
+        .map(|_| read_string())
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(Some(Checkpoint {
+        log_offset,
+        root,
+        leaves,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{self, OpenOptions};
+    use std::io::Write;
+
+    use super::{DurableMerkleTree, CHECKPOINT_FILE, CHECKPOINT_TMP_FILE, LOG_FILE};
+    use crate::errors::WalErrors;
+    use crate::merkle_tree::{MerkleTree, Padding};
+
+    fn add_texts(durable: &mut DurableMerkleTree, texts: std::ops::Range<usize>) {
+        for i in texts {
+            durable.add_unhashed(i.to_string()).unwrap();
+        }
+    }
+
+    fn expected_root(amount: usize) -> Option<String> {
+        let texts: Vec<String> = (0..amount).map(|i| i.to_string()).collect();
+        MerkleTree::build(texts.iter().map(|t| t.as_str()).collect(), true).root()
+    }
+
+    #[test]
+    fn test_01_reopening_replays_the_log() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let mut durable = DurableMerkleTree::open(dir.path(), MerkleTree::new()).unwrap();
+        add_texts(&mut durable, 0..10);
+        drop(durable);
+
+        let durable = DurableMerkleTree::open(dir.path(), MerkleTree::new()).unwrap();
+        assert_eq!(10, durable.tree().len());
+        assert_eq!(expected_root(10), durable.tree().root());
+        assert!(!dir.path().join(CHECKPOINT_FILE).exists());
+    }
+
+    #[test]
+    fn test_02_torn_writes_are_truncated() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut durable = DurableMerkleTree::open(dir.path(), MerkleTree::new()).unwrap();
+        add_texts(&mut durable, 0..5);
+        drop(durable);
+
+        // A crash in the middle of an add leaves half an entry at the end
+        let log_path = dir.path().join(LOG_FILE);
+        let complete_len = fs::metadata(&log_path).unwrap().len();
+        let mut log = OpenOptions::new().append(true).open(&log_path).unwrap();
+        log.write_all(&[64, 0, 0, 0, 1, 2, 3, 4, b'a', b'b'])
+            .unwrap();
+        drop(log);
+
+        let mut durable = DurableMerkleTree::open(dir.path(), MerkleTree::new()).unwrap();
+        assert_eq!(expected_root(5), durable.tree().root());
+        assert_eq!(complete_len, fs::metadata(&log_path).unwrap().len());
+
+        add_texts(&mut durable, 5..8);
+        drop(durable);
+        let durable = DurableMerkleTree::open(dir.path(), MerkleTree::new()).unwrap();
+        assert_eq!(expected_root(8), durable.tree().root());
+    }
+
+    #[test]
+    fn test_03_recovery_starts_from_the_last_checkpoint() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut durable = DurableMerkleTree::open(dir.path(), MerkleTree::new())
+            .unwrap()
+            .with_checkpoint_interval(4);
+        add_texts(&mut durable, 0..10);
+        drop(durable);
+        assert!(dir.path().join(CHECKPOINT_FILE).exists());
+
+        // The last entry is corrupted: the 8 leaves of the checkpoint and the one before it are kept
+        let log_path = dir.path().join(LOG_FILE);
+        let mut log = fs::read(&log_path).unwrap();
+        let last = log.len() - 1;
+        log[last] ^= 1;
+        fs::write(&log_path, log).unwrap();
+
+        let durable = DurableMerkleTree::open(dir.path(), MerkleTree::new()).unwrap();
+        assert_eq!(9, durable.tree().len());
+        assert_eq!(expected_root(9), durable.tree().root());
+    }
+
+    #[test]
+    fn test_04_checkpoint_root_must_match_the_recomputed_one() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut durable = DurableMerkleTree::open(dir.path(), MerkleTree::new()).unwrap();
+        add_texts(&mut durable, 0..3);
+        durable.checkpoint().unwrap();
+        drop(durable);
+
+        // With another padding, the same leaves lead to another root
+        let promoted = MerkleTree::new().with_padding(Padding::Promote);
+        assert!(matches!(
+            DurableMerkleTree::open(dir.path(), promoted),
+            Err(WalErrors::RootMismatchError(_))
+        ));
+
+        let checkpoint_path = dir.path().join(CHECKPOINT_FILE);
+        let mut checkpoint = fs::read(&checkpoint_path).unwrap();
+        checkpoint[30] ^= 1;
+        fs::write(&checkpoint_path, checkpoint).unwrap();
+        assert!(matches!(
+            DurableMerkleTree::open(dir.path(), MerkleTree::new()),
+            Err(WalErrors::CorruptCheckpointError(_))
+        ));
+    }
+
+    #[test]
+    fn test_05_damaged_entries_in_the_middle_are_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut durable = DurableMerkleTree::open(dir.path(), MerkleTree::new()).unwrap();
+        add_texts(&mut durable, 0..5);
+        drop(durable);
+
+        // The last byte of the second entry, with three whole entries after it
+        let log_path = dir.path().join(LOG_FILE);
+        let mut log = fs::read(&log_path).unwrap();
+        let log_len = log.len();
+        log[2 * (log_len / 5) - 1] ^= 1;
+        fs::write(&log_path, &log).unwrap();
+
+        assert!(matches!(
+            DurableMerkleTree::open(dir.path(), MerkleTree::new()),
+            Err(WalErrors::CorruptLogError(_))
+        ));
+        assert_eq!(log, fs::read(&log_path).unwrap());
+    }
+
+    #[test]
+    fn test_06_adds_succeed_when_the_checkpoint_fails() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut durable = DurableMerkleTree::open(dir.path(), MerkleTree::new())
+            .unwrap()
+            .with_checkpoint_interval(2);
+
+        // A directory in the way of the temporary checkpoint makes it fail
+        let blocker = dir.path().join(CHECKPOINT_TMP_FILE);
+        fs::create_dir(&blocker).unwrap();
+        add_texts(&mut durable, 0..3);
+        assert!(durable.take_checkpoint_error().is_some());
+        assert!(durable.take_checkpoint_error().is_none());
+        assert_eq!(expected_root(3), durable.tree().root());
+        assert!(!dir.path().join(CHECKPOINT_FILE).exists());
+
+        fs::remove_dir(&blocker).unwrap();
+        add_texts(&mut durable, 3..4);
+        assert!(durable.take_checkpoint_error().is_none());
+        assert!(dir.path().join(CHECKPOINT_FILE).exists());
+        drop(durable);
+
+        let durable = DurableMerkleTree::open(dir.path(), MerkleTree::new()).unwrap();
+        assert_eq!(expected_root(4), durable.tree().root());
+    }
+}
@@ -0,0 +1,71 @@
+use wasm_bindgen::prelude::wasm_bindgen;
+
+use crate::hashing::DomainSeparation;
+use crate::merkle_tree::{MerkleTree, Padding};
+use crate::proof::MerkleProof;
+
+/// The tree for JavaScript, as `MerkleTree`. Its hashes are hex strings, the same the REPL shows
+#[wasm_bindgen(js_name = MerkleTree)]
+pub struct WasmMerkleTree {
+    tree: MerkleTree,
+}
+
+#[wasm_bindgen(js_class = MerkleTree)]
+impl WasmMerkleTree {
+    /// `new MerkleTree(["a", "b"], true)` hashes the strings first, with false they must already be hashes
+    #[wasm_bindgen(constructor)]
+    pub fn new(values: Vec<String>, unhashed: bool) -> WasmMerkleTree {
+        let values = values.iter().map(String::as_str).collect();
+        WasmMerkleTree {
+            tree: MerkleTree::build(values, unhashed),
+        }
+    }
+
+    pub fn add(&mut self, hash: String) {
+        self.tree.add(hash);
+    }
+
+    #[wasm_bindgen(js_name = addUnhashed)]
+    pub fn add_unhashed(&mut self, text: String) {
+        self.tree.add_unhashed(text);
+    }
+
+    /// undefined for an empty tree
+    pub fn root(&self) -> Option<String> {
+        self.tree.root()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn size(&self) -> usize {
+        self.tree.len()
+    }
+
+    /// The siblings of the leaf from the bottom to the top, undefined if there is no leaf at the index
+    #[wasm_bindgen(js_name = generateProof)]
+    pub fn generate_proof(&self, index: usize) -> Option<Vec<String>> {
+        self.tree.prove(index).map(|proof| proof.siblings)
+    }
+
+    pub fn verify(&self, proof: Vec<String>, leaf: String, index: usize) -> bool {
+        self.tree.verify(proof, leaf, index)
+    }
+}
+
+/// For clients that only have the root: checks a proof generated by a tree with the default padding and without
+/// domain separation
+#[wasm_bindgen(js_name = verifyProof)]
+pub fn verify_proof(
+    root: &str,
+    leaf: String,
+    index: usize,
+    tree_size: usize,
+    proof: Vec<String>,
+) -> bool {
+    let proof = MerkleProof {
+        index,
+        tree_size,
+        leaf,
+        siblings: proof,
+    };
+    proof.verify(root, Padding::DuplicateLast, &DomainSeparation::default())
+}
@@ -0,0 +1,85 @@
+use std::error::Error;
+
+use rusty_merkle_tree::{
+    DomainSeparation, MerkleProof, MerkleTree, MerkleTreeErrors, Padding, SharedMerkleTree,
+};
+
+const HASH_A: &str = "ca978112ca1bbdcafac231b39a23dc4da786eff8147c4e72b9807785afee48bb";
+const HASH_B: &str = "3e23e8160039594a33894f6564e1b1348bbd7a0088d42c4acb73eeaed59c009d";
+
+#[test]
+fn test_01_trees_are_built_and_proven_from_outside_the_crate() {
+    let mut tree = MerkleTree::new().with_leaf_index();
+    tree.add(HASH_A.to_string());
+    tree.add_unhashed("b".to_string());
+
+    assert_eq!(2, tree.len());
+    assert_eq!(Some(1), tree.index_of(HASH_B));
+    assert_eq!(
+        Some("62af5c3cb8da3e4f25061e829ebeea5c7513c54949115b1acc225930a90154da".to_string()),
+        tree.root()
+    );
+
+    let proof = tree.prove(0).unwrap();
+    assert_eq!(
+        MerkleProof {
+            index: 0,
+            tree_size: 2,
+            leaf: HASH_A.to_string(),
+            siblings: vec![HASH_B.to_string()],
+        },
+        proof
+    );
+    assert!(tree.verify(proof.siblings.clone(), proof.leaf.clone(), 0));
+}
+
+#[test]
+fn test_02_proofs_are_verified_by_someone_holding_only_the_root() {
+    let domain = DomainSeparation::rfc6962();
+    let tree = MerkleTree::build(vec!["a", "b", "c", "d", "e", "f"], true)
+        .with_padding(Padding::Promote)
+        .with_domain_separation(domain.clone());
+    let root = tree.root().unwrap();
+    let proofs: Vec<MerkleProof> = (0..tree.len()).map(|i| tree.prove(i).unwrap()).collect();
+    drop(tree);
+
+    for proof in &proofs {
+        assert!(proof.verify(&root, Padding::Promote, &domain));
+        assert!(!proof.verify(&root, Padding::Promote, &DomainSeparation::default()));
+    }
+    let forged = MerkleProof {
+        leaf: HASH_A.to_string(),
+        ..proofs[1].clone()
+    };
+    assert!(!forged.verify(&root, Padding::Promote, &domain));
+    // Leaf 4 has no sibling at the second level with promote padding, so its proof is not valid for zero padding
+    assert!(!proofs[4].verify(&root, Padding::Zero, &domain));
+}
+
+#[test]
+fn test_03_snapshots_and_shared_trees_agree_with_the_tree() {
+    let shared = SharedMerkleTree::new(MerkleTree::build(vec!["a", "b", "c"], true));
+    let snapshot = shared.snapshot();
+    shared.add_unhashed("d".to_string());
+
+    assert_eq!(3, snapshot.len());
+    assert_ne!(shared.root().as_deref(), snapshot.root());
+    let proof = snapshot.generate_proof(2);
+    assert!(snapshot.verify(proof, snapshot.leaf(2).unwrap().to_string(), 2));
+}
+
+#[test]
+fn test_04_errors_can_be_boxed_and_displayed() {
+    let error: Box<dyn Error> = DomainSeparation::new("tag", "tag-node")
+        .map(|_| ())
+        .unwrap_err()
+        .into();
+    assert_eq!(
+        "a domain tag can't be a prefix of the other",
+        error.to_string()
+    );
+    assert!(matches!(
+        DomainSeparation::new("leaf", "leaf"),
+        Err(MerkleTreeErrors::AmbiguousDomainTagsError)
+    ));
+}
@@ -0,0 +1,157 @@
+use proptest::prelude::*;
+use sha2::{Digest, Sha256};
+
+use rusty_merkle_tree::{DomainSeparation, MerkleTree, Padding};
+
+const ZERO_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+fn sha256_hex(parts: &[&[u8]]) -> String {
+    let mut hasher = Sha256::new();
+    for part in parts {
+        hasher.update(part);
+    }
+    hex::encode(hasher.finalize())
+}
+
+/// The root computed the obvious way, level by level, without the tree. Only shares the definitions with it: a node
+/// is the hash of its node tag and the hex of its children, and the levels are completed according to the padding
+fn reference_root(leaves: &[String], padding: Padding, domain: &DomainSeparation) -> String {
+    let mut level: Vec<String> = leaves
+        .iter()
+        .map(|leaf| sha256_hex(&[domain.leaf_tag(), leaf.as_bytes()]))
+        .collect();
+
+    let width = level.len().next_power_of_two().max(2);
+    match padding {
+        Padding::DuplicateLast => level.resize(width, level[level.len() - 1].clone()),
+        Padding::Zero => level.resize(width, ZERO_HASH.to_string()),
+        Padding::Promote => {}
+    }
+
+    loop {
+        level = level
+            .chunks(2)
+            .map(|pair| match pair {
+                [left, right] => {
+                    sha256_hex(&[domain.node_tag(), left.as_bytes(), right.as_bytes()])
+                }
+                // Only with promote padding a node lacks its sibling, and it goes up unchanged
+                [alone] => alone.clone(),
+                _ => unreachable!(),
+            })
+            .collect();
+        if level.len() == 1 {
+            return level.remove(0);
+        }
+    }
+}
+
+/// Distinct leaves, so a proof can't be valid for another position just because the leaves are equal
+fn leaves() -> impl Strategy<Value = Vec<String>> {
+    (1..70usize, any::<u32>())
+        .prop_map(|(amount, seed)| (0..amount).map(|i| format!("{seed}-{i}")).collect())
+}
+
+fn padding() -> impl Strategy<Value = Padding> {
+    prop_oneof![
+        Just(Padding::DuplicateLast),
+        Just(Padding::Zero),
+        Just(Padding::Promote)
+    ]
+}
+
+fn domain() -> impl Strategy<Value = DomainSeparation> {
+    prop_oneof![
+        Just(DomainSeparation::default()),
+        Just(DomainSeparation::rfc6962())
+    ]
+}
+
+fn tree_of(leaves: &[String], padding: Padding, domain: &DomainSeparation) -> MerkleTree {
+    let mut tree = MerkleTree::new()
+        .with_padding(padding)
+        .with_domain_separation(domain.clone());
+    for leaf in leaves {
+        tree.add_unhashed(leaf.clone());
+    }
+    tree
+}
+
+proptest! {
+    #[test]
+    fn test_01_every_proof_verifies(leaves in leaves(), padding in padding(), domain in domain()) {
+        let tree = tree_of(&leaves, padding, &domain);
+        let root = tree.root().unwrap();
+
+        for index in 0..leaves.len() {
+            let proof = tree.generate_proof(index);
+            let leaf = tree.leaf(index).unwrap();
+            prop_assert!(tree.verify(proof, leaf, index));
+            prop_assert!(tree.prove(index).unwrap().verify(&root, padding, &domain));
+        }
+    }
+
+    #[test]
+    fn test_02_tampered_proofs_dont_verify(
+        leaves in leaves(),
+        padding in padding(),
+        domain in domain(),
+        index in any::<prop::sample::Index>(),
+        other_index in any::<prop::sample::Index>(),
+        element in any::<prop::sample::Index>(),
+    ) {
+        let tree = tree_of(&leaves, padding, &domain);
+        let index = index.index(leaves.len());
+        let proof = tree.generate_proof(index);
+        let leaf = tree.leaf(index).unwrap();
+
+        let other_leaf = sha256_hex(&[domain.leaf_tag(), b"not in the tree"]);
+        prop_assert!(!tree.verify(proof.clone(), other_leaf, index));
+
+        let other_index = other_index.index(leaves.len());
+        if other_index != index {
+            prop_assert!(!tree.verify(proof.clone(), leaf.clone(), other_index));
+        }
+        prop_assert!(!tree.verify(proof.clone(), leaf.clone(), leaves.len()));
+
+        if !proof.is_empty() {
+            let mut tampered = proof.clone();
+            let element = element.index(proof.len());
+            let flipped = if tampered[element].starts_with('0') { "1" } else { "0" };
+            tampered[element].replace_range(0..1, flipped);
+            prop_assert!(!tree.verify(tampered, leaf.clone(), index));
+
+            let mut shorter = proof.clone();
+            shorter.pop();
+            prop_assert!(!tree.verify(shorter, leaf.clone(), index));
+        }
+        let mut longer = proof;
+        longer.push(ZERO_HASH.to_string());
+        prop_assert!(!tree.verify(longer, leaf, index));
+    }
+
+    #[test]
+    fn test_03_adding_leaves_matches_the_reference_root(
+        leaves in leaves(),
+        padding in padding(),
+        domain in domain(),
+    ) {
+        let mut tree = MerkleTree::new()
+            .with_padding(padding)
+            .with_domain_separation(domain.clone());
+
+        // Checking after every add covers all the sizes up to the amount of leaves, crossing powers of two
+        for (added, leaf) in leaves.iter().enumerate() {
+            tree.add_unhashed(leaf.clone());
+            let expected = reference_root(&leaves[..=added], padding, &domain);
+            prop_assert_eq!(Some(expected), tree.root());
+        }
+
+        // build hashes the leaves without domain separation, so it gets them already hashed
+        let hashed = tree.leaves();
+        let built = MerkleTree::build(hashed.iter().map(String::as_str).collect(), false)
+            .with_padding(padding)
+            .with_domain_separation(domain);
+        prop_assert_eq!(tree.root(), built.root());
+    }
+}
@@ -0,0 +1,146 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::process::{Child, Command, Stdio};
+
+use serde_json::{json, Value};
+
+/// The server binary listening on a free port, killed when dropped
+struct TestServer {
+    process: Child,
+    address: String,
+}
+
+impl TestServer {
+    fn start(leaves_file: Option<&std::path::Path>) -> Self {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_server"));
+        command.args(["--address", "127.0.0.1:0"]);
+        if let Some(file) = leaves_file {
+            command.arg(file);
+        }
+        let mut process = command.stdout(Stdio::piped()).spawn().unwrap();
+
+        let mut line = String::new();
+        BufReader::new(process.stdout.take().unwrap())
+            .read_line(&mut line)
+            .unwrap();
+        let address = line
+            .trim()
+            .strip_prefix("Listening on http://")
+            .unwrap()
+            .to_string();
+
+        TestServer { process, address }
+    }
+
+    fn request(&self, method: &str, path: &str, body: Option<Value>) -> (u16, Value) {
+        let body = body.map(|body| body.to_string()).unwrap_or_default();
+        let mut stream = TcpStream::connect(&self.address).unwrap();
+        write!(
+            stream,
+            "{method} {path} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\nContent-Length: {}\r\n\r\n{body}",
+            self.address,
+            body.len()
+        )
+        .unwrap();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let status = response[9..12].parse().unwrap();
+        let (_, body) = response.split_once("\r\n\r\n").unwrap();
+        (status, serde_json::from_str(body).unwrap())
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        let _ = self.process.kill();
+        let _ = self.process.wait();
+    }
+}
+
+const HASH_A: &str = "ca978112ca1bbdcafac231b39a23dc4da786eff8147c4e72b9807785afee48bb";
+const HASH_B: &str = "3e23e8160039594a33894f6564e1b1348bbd7a0088d42c4acb73eeaed59c009d";
+
+#[test]
+fn test_01_tree_is_loaded_from_a_file_and_served() {
+    let dir = tempfile::tempdir().unwrap();
+    let leaves_file = dir.path().join("leaves");
+    std::fs::write(&leaves_file, format!("{HASH_A}\n{HASH_B}\n")).unwrap();
+    let server = TestServer::start(Some(&leaves_file));
+
+    let (status, root) = server.request("GET", "/root", None);
+    assert_eq!(200, status);
+    assert_eq!(2, root["size"]);
+    assert_eq!(
+        "62af5c3cb8da3e4f25061e829ebeea5c7513c54949115b1acc225930a90154da",
+        root["root"]
+    );
+
+    let (status, leaf) = server.request("GET", "/leaf/1", None);
+    assert_eq!((200, json!(HASH_B)), (status, leaf["leaf"].clone()));
+
+    let (status, proof) = server.request("GET", "/proof/0", None);
+    assert_eq!(200, status);
+    assert_eq!(json!([HASH_B]), proof["proof"]);
+    assert_eq!(root["root"], proof["root"]);
+
+    assert_eq!(404, server.request("GET", "/proof/2", None).0);
+    assert_eq!(400, server.request("GET", "/leaf/x", None).0);
+    assert_eq!(404, server.request("GET", "/nothing", None).0);
+}
+
+#[test]
+fn test_02_appended_leaves_get_proofs_that_verify() {
+    let server = TestServer::start(None);
+    assert_eq!(Value::Null, server.request("GET", "/root", None).1["root"]);
+
+    let (status, appended) = server.request(
+        "POST",
+        "/leaves",
+        Some(json!({ "unhashed": ["a", "b", "c"] })),
+    );
+    assert_eq!(200, status);
+    assert_eq!(0, appended["first_index"]);
+    assert_eq!(3, appended["size"]);
+    let (_, appended) = server.request("POST", "/leaves", Some(json!({ "leaves": [HASH_A] })));
+    assert_eq!(3, appended["first_index"]);
+
+    let (_, proof) = server.request("GET", "/proof/1", None);
+    assert_eq!(HASH_B, proof["leaf"]);
+    assert_eq!(appended["root"], proof["root"]);
+
+    let mut request = json!({ "proof": proof["proof"], "leaf": HASH_B, "index": 1 });
+    let (status, verified) = server.request("POST", "/verify", Some(request.clone()));
+    assert_eq!((200, json!(true)), (status, verified["valid"].clone()));
+
+    request["index"] = json!(2);
+    assert_eq!(
+        json!(false),
+        server.request("POST", "/verify", Some(request)).1["valid"]
+    );
+
+    assert_eq!(
+        400,
+        server
+            .request("POST", "/leaves", Some(json!({ "leaves": [1] })))
+            .0
+    );
+    assert_eq!(400, server.request("POST", "/verify", Some(json!({}))).0);
+    assert_eq!(405, server.request("GET", "/verify", None).0);
+}
+
+#[test]
+fn test_03_bodies_over_the_limit_are_rejected() {
+    let server = TestServer::start(None);
+
+    // The JSON around the leaf takes the body over 1 MiB
+    let leaf = "a".repeat(1 << 20);
+    let (status, rejected) = server.request("POST", "/leaves", Some(json!({ "unhashed": [leaf] })));
+    assert_eq!(413, status);
+    assert!(rejected["error"].is_string());
+    assert_eq!(0, server.request("GET", "/root", None).1["size"]);
+
+    let leaf = "a".repeat(1000);
+    let (status, _) = server.request("POST", "/leaves", Some(json!({ "unhashed": [leaf] })));
+    assert_eq!(200, status);
+}
@@ -0,0 +1,140 @@
+//! Known answers computed outside this crate, in tests/vectors. The roots and proofs of the tree must match them
+
+use serde_json::Value;
+
+use rusty_merkle_tree::bitcoin::{self, PartialMerkleTree};
+use rusty_merkle_tree::hashing;
+use rusty_merkle_tree::{ConsistencyProof, DomainSeparation, MerkleTree, NodeEncoding, Padding};
+
+fn load(vectors: &str) -> Value {
+    serde_json::from_str(vectors).unwrap()
+}
+
+fn strings(value: &Value) -> Vec<String> {
+    value
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|string| string.as_str().unwrap().to_string())
+        .collect()
+}
+
+fn number(value: &Value) -> usize {
+    value.as_u64().unwrap() as usize
+}
+
+/// The tree of RFC 6962: leaf and node tags, the children hashed as bytes and the promote padding
+fn rfc6962_domain() -> DomainSeparation {
+    DomainSeparation::rfc6962().with_node_encoding(NodeEncoding::Raw)
+}
+
+/// One tree per size, from 0 up to all the leaves of the vectors
+fn rfc6962_trees(vectors: &Value) -> Vec<MerkleTree> {
+    let domain = rfc6962_domain();
+    let leaves: Vec<String> = strings(&vectors["leaves"])
+        .iter()
+        .map(|leaf| hashing::hash_leaf(&domain, &hex::decode(leaf).unwrap()))
+        .collect();
+
+    (0..=leaves.len())
+        .map(|size| {
+            MerkleTree::new()
+                .with_padding(Padding::Promote)
+                .with_domain_separation(domain.clone())
+                .with_leaves(leaves[..size].to_vec())
+        })
+        .collect()
+}
+
+#[test]
+fn test_01_rfc6962_roots() {
+    let vectors = load(include_str!("vectors/rfc6962.json"));
+    let trees = rfc6962_trees(&vectors);
+
+    for vector in vectors["roots"].as_array().unwrap() {
+        let tree = &trees[number(&vector["tree_size"])];
+        assert_eq!(vector["root"].as_str(), tree.root().as_deref());
+    }
+
+    // Adding the leaves one by one goes through the same roots
+    let mut added = MerkleTree::new()
+        .with_padding(Padding::Promote)
+        .with_domain_separation(rfc6962_domain());
+    for (leaf, vector) in trees
+        .last()
+        .unwrap()
+        .leaves()
+        .into_iter()
+        .zip(vectors["roots"].as_array().unwrap())
+    {
+        added.add(leaf);
+        assert_eq!(vector["root"].as_str(), added.root().as_deref());
+    }
+}
+
+#[test]
+fn test_02_rfc6962_audit_paths() {
+    let vectors = load(include_str!("vectors/rfc6962.json"));
+    let trees = rfc6962_trees(&vectors);
+
+    for vector in vectors["audit_paths"].as_array().unwrap() {
+        let tree = &trees[number(&vector["tree_size"])];
+        let index = number(&vector["index"]);
+        let path = strings(&vector["path"]);
+
+        let proof = tree.prove(index).unwrap();
+        assert_eq!(path, proof.siblings, "{vector}");
+        assert!(proof.verify(&tree.root().unwrap(), Padding::Promote, &rfc6962_domain()));
+        assert!(tree.verify(path, tree.leaf(index).unwrap(), index));
+    }
+}
+
+#[test]
+fn test_03_rfc6962_consistency_proofs() {
+    let vectors = load(include_str!("vectors/rfc6962.json"));
+    let trees = rfc6962_trees(&vectors);
+
+    for vector in vectors["consistency_proofs"].as_array().unwrap() {
+        let old_tree = &trees[number(&vector["old_size"])];
+        let new_tree = &trees[number(&vector["new_size"])];
+        let expected = ConsistencyProof {
+            old_size: old_tree.len(),
+            new_size: new_tree.len(),
+            hashes: strings(&vector["proof"]),
+        };
+
+        let proof = new_tree.prove_consistency(old_tree.len()).unwrap();
+        assert_eq!(expected, proof, "{vector}");
+        assert!(proof.verify(
+            &old_tree.root().unwrap(),
+            &new_tree.root().unwrap(),
+            &rfc6962_domain()
+        ));
+    }
+}
+
+#[test]
+fn test_04_bitcoin_block_roots() {
+    let vectors = load(include_str!("vectors/bitcoin.json"));
+
+    for block in vectors["blocks"].as_array().unwrap() {
+        let txids: Vec<[u8; 32]> = strings(&block["txids"])
+            .iter()
+            .map(|txid| bitcoin::txid_from_hex(txid).unwrap())
+            .collect();
+        let root = bitcoin::txid_from_hex(block["merkle_root"].as_str().unwrap()).unwrap();
+        assert_eq!(
+            Some(root),
+            bitcoin::merkle_root(&txids),
+            "{}",
+            block["name"]
+        );
+
+        // A merkle block proving every transaction leads to the same root
+        let matches = vec![true; txids.len()];
+        let partial_tree = PartialMerkleTree::build(&txids, &matches).unwrap();
+        let (partial_root, matched) = partial_tree.extract_matches().unwrap();
+        assert_eq!(root, partial_root, "{}", block["name"]);
+        assert_eq!(txids.len(), matched.len());
+    }
+}